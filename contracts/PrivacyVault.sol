@@ -3,6 +3,7 @@ pragma solidity ^0.8.23;
 
 import "./libraries/MerkleTree.sol";
 import "./libraries/PoseidonT3.sol";
+import "./libraries/WithdrawalPublicInputs.sol";
 import "./ZKVerifier.sol";
 import "./ASPRegistry.sol";
 
@@ -124,11 +125,14 @@ contract PrivacyVault {
         if (nullifierHashes[nullifierHash]) revert NullifierAlreadyUsed();
         if (!isKnownRoot(root)) revert InvalidMerkleRoot();
 
-        bytes32[] memory publicInputs = new bytes32[](4);
-        publicInputs[0] = root;
-        publicInputs[1] = nullifierHash;
-        publicInputs[2] = bytes32(uint256(uint160(address(recipient))));
-        publicInputs[3] = bytes32(amount);
+        bytes32[] memory publicInputs = WithdrawalPublicInputs.encode(
+            root,
+            nullifierHash,
+            recipient,
+            amount,
+            address(this),
+            block.chainid
+        );
 
         if (!zkVerifier.verifyProof(zkProof, publicInputs)) {
             revert InvalidProof();
@@ -169,11 +173,14 @@ contract PrivacyVault {
             revert InvalidASPProof();
         }
 
-        bytes32[] memory publicInputs = new bytes32[](4);
-        publicInputs[0] = root;
-        publicInputs[1] = nullifierHash;
-        publicInputs[2] = bytes32(uint256(uint160(address(recipient))));
-        publicInputs[3] = bytes32(amount);
+        bytes32[] memory publicInputs = WithdrawalPublicInputs.encode(
+            root,
+            nullifierHash,
+            recipient,
+            amount,
+            address(this),
+            block.chainid
+        );
 
         if (!zkVerifier.verifyProof(zkProof, publicInputs)) {
             revert InvalidProof();
@@ -0,0 +1,24 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.23;
+
+/// @notice Instance column of the withdrawal circuit, one big-endian word per
+/// row. Must stay in step with `WithdrawalPublicInputs::evm_words` in
+/// zk-circuits/src/withdrawal_circuit.rs.
+library WithdrawalPublicInputs {
+    function encode(
+        bytes32 root,
+        bytes32 nullifierHash,
+        address recipient,
+        uint256 amount,
+        address vault,
+        uint256 chainId
+    ) internal pure returns (bytes32[] memory publicInputs) {
+        publicInputs = new bytes32[](6);
+        publicInputs[0] = root;
+        publicInputs[1] = nullifierHash;
+        publicInputs[2] = bytes32(uint256(uint160(recipient)));
+        publicInputs[3] = bytes32(amount);
+        publicInputs[4] = bytes32(uint256(uint160(vault)));
+        publicInputs[5] = bytes32(chainId);
+    }
+}
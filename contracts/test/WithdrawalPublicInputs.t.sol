@@ -0,0 +1,27 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.23;
+
+import "../libraries/WithdrawalPublicInputs.sol";
+
+/// Same vector as `test_instances_match_contract_public_inputs` in
+/// zk-circuits/src/withdrawal_circuit.rs, whose instances must equal these words.
+contract WithdrawalPublicInputsTest {
+    function testEncodingMatchesCircuitInstances() public pure {
+        bytes32[] memory words = WithdrawalPublicInputs.encode(
+            bytes32(uint256(0x0a0b0c)),
+            bytes32(uint256(0x010203)),
+            address(uint160(uint256(0x0000000000000000000000000102030405060708090a0b0c0d0e0f1011121314))),
+            1 ether,
+            address(uint160(uint256(0x0000000000000000000000005215d0bf334668c5722bc94fef1f82d95443cf57))),
+            845320009
+        );
+
+        require(words.length == 6, "length");
+        require(words[0] == 0x00000000000000000000000000000000000000000000000000000000000a0b0c, "root");
+        require(words[1] == 0x0000000000000000000000000000000000000000000000000000000000010203, "nullifier");
+        require(words[2] == 0x0000000000000000000000000102030405060708090a0b0c0d0e0f1011121314, "recipient");
+        require(words[3] == 0x0000000000000000000000000000000000000000000000000de0b6b3a7640000, "amount");
+        require(words[4] == 0x0000000000000000000000005215d0bf334668c5722bc94fef1f82d95443cf57, "vault");
+        require(words[5] == 0x0000000000000000000000000000000000000000000000000000000032628f49, "chain id");
+    }
+}
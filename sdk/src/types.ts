@@ -17,6 +17,9 @@ export interface WithdrawalRequest {
   merklePath: Uint8Array[];
   pathIndices: boolean[];
   secret?: Uint8Array;
+  /** Vault and chain the proof is bound to; it will not verify elsewhere. */
+  vaultAddress: string;
+  chainId: number;
 }
 
 export interface WithdrawalResult {
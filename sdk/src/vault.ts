@@ -145,6 +145,8 @@ export class PrivacyVaultSDK {
       merklePath: [],
       pathIndices: [],
       secret: note.secret,
+      vaultAddress: this.config.vaultAddress,
+      chainId: this.config.chainId,
     });
 
     const tx = await this.vault.withdraw(
@@ -62,6 +62,12 @@ export class ZKProofClient {
   private async generateRealProof(
     request: WithdrawalRequest
   ): Promise<WithdrawalResult> {
+    if (!request.vaultAddress || !request.chainId) {
+      throw new Error(
+        "Withdrawal request must name the vaultAddress and chainId the proof is bound to."
+      );
+    }
+
     const wasmRequest = {
       secret: Array.from(request.commitment),
       nullifier_seed: Array.from(request.nullifier),
@@ -73,6 +79,8 @@ export class ZKProofClient {
         ? Array.from(request.merkleRoot)
         : new Array(32).fill(0),
       recipient: this.addressToBytes(request.recipient),
+      vault_address: this.addressToBytes(request.vaultAddress),
+      chain_id: request.chainId,
     };
 
     const resultJson = wasmModule!.generate_withdrawal_proof(
@@ -95,6 +103,7 @@ export class ZKProofClient {
 
   async generateComplianceProof(
     commitment: Uint8Array,
+    secret: Uint8Array,
     associationPath: Uint8Array[],
     pathIndices: boolean[],
     associationRoot: Uint8Array
@@ -107,6 +116,7 @@ export class ZKProofClient {
 
     const request = {
       commitment: Array.from(commitment),
+      secret: Array.from(secret),
       association_path: associationPath.map((p) => Array.from(p)),
       path_indices: pathIndices,
       association_root: Array.from(associationRoot),
@@ -1,5 +1,10 @@
 import { NextRequest, NextResponse } from "next/server";
 import { keccak256 } from "ethers";
+import { MERKLE_TREE_DEPTH } from "@/lib/merkle-sha256";
+
+// Must match LEAF_DOMAIN / NODE_DOMAIN in zk-circuits/src/merkle.rs.
+const LEAF_DOMAIN = 0x00;
+const NODE_DOMAIN = 0x01;
 
 interface WithdrawalRequest {
   commitment: number[];
@@ -133,7 +138,10 @@ function verifyMerklePath(
   indices: boolean[],
   root: Uint8Array
 ): boolean {
-  let current = leaf;
+  if (path.length !== MERKLE_TREE_DEPTH || indices.length !== MERKLE_TREE_DEPTH) {
+    return false;
+  }
+  let current = hashLeaf(leaf);
   for (let i = 0; i < path.length; i++) {
     const sibling = path[i];
     if (indices[i]) {
@@ -145,8 +153,14 @@ function verifyMerklePath(
   return arraysEqual(current, root);
 }
 
+function hashLeaf(leaf: Uint8Array): Uint8Array {
+  const combined = new Uint8Array([LEAF_DOMAIN, ...leaf]);
+  const hash = keccak256(combined);
+  return hexToBytes(hash);
+}
+
 function hashPair(left: Uint8Array, right: Uint8Array): Uint8Array {
-  const combined = new Uint8Array([...left, ...right]);
+  const combined = new Uint8Array([NODE_DOMAIN, ...left, ...right]);
   const hash = keccak256(combined);
   return hexToBytes(hash);
 }
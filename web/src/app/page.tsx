@@ -231,6 +231,7 @@ export default function Home() {
 
         const proofResult = await zkClient.generateComplianceProof(
           sdkNote.commitment,
+          sdkNote.secret,
           path,
           indices,
           root
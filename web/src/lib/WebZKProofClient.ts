@@ -186,6 +186,7 @@ export class WebZKProofClient extends ZKProofClient {
 
   async generateComplianceProof(
     commitment: Uint8Array,
+    _secret: Uint8Array,
     associationPath: Uint8Array[],
     pathIndices: boolean[],
     associationRoot: Uint8Array
@@ -2,6 +2,10 @@ import { sha256, concat } from "ethers";
 
 export const MERKLE_TREE_DEPTH = 20;
 
+// Must match LEAF_DOMAIN / NODE_DOMAIN in zk-circuits/src/merkle.rs.
+const LEAF_DOMAIN = "0x00";
+const NODE_DOMAIN = "0x01";
+
 export class MerkleTreeSHA256 {
   private leaves: string[];
   private layers: string[][];
@@ -27,8 +31,12 @@ export class MerkleTreeSHA256 {
     return zeros;
   }
 
+  private hashLeaf(leaf: string): string {
+    return sha256(concat([LEAF_DOMAIN, leaf]));
+  }
+
   private hashPair(left: string, right: string): string {
-    return sha256(concat([left, right]));
+    return sha256(concat([NODE_DOMAIN, left, right]));
   }
 
   private rebuild(): void {
@@ -44,7 +52,7 @@ export class MerkleTreeSHA256 {
     const indices: boolean[] = [];
 
     let currentIndex = index;
-    let currentLevelValues = [...this.leaves];
+    let currentLevelValues = this.leaves.map((l) => this.hashLeaf(l));
 
     for (let level = 0; level < this.depth; level++) {
       const isRight = currentIndex % 2 === 1;
@@ -81,7 +89,7 @@ export class MerkleTreeSHA256 {
   }
 
   public getRoot(): string {
-    let currentLevelValues = [...this.leaves];
+    let currentLevelValues = this.leaves.map((l) => this.hashLeaf(l));
     for (let level = 0; level < this.depth; level++) {
       const nextLevelValues: string[] = [];
       for (let i = 0; i < currentLevelValues.length; i += 2) {
@@ -2,34 +2,120 @@ use std::marker::PhantomData;
 use ff::PrimeField;
 use halo2_proofs::{
     circuit::{Layouter, SimpleFloorPlanner, Value},
-    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Expression, Fixed, Instance, Selector},
-    poly::Rotation,
+    halo2curves::bn256::Fr,
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Instance},
 };
 use serde::{Serialize, Deserialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::encoding::{field_from_bytes, field_from_canonical_bytes, field_to_bytes};
+use crate::merkle::fold_merkle_path;
+use crate::merkle_path::{MerklePathChip, MerklePathConfig};
+use crate::poseidon::{poseidon_hash_native, PoseidonChip, PoseidonConfig};
+use crate::withdrawal_circuit::zero_hashes;
+use crate::CircuitError;
 
 pub const ASSOCIATION_DEPTH: usize = 10;
 
+/// Rows of the instance column; see `AssociationPublicInputs::instances`.
+const ASSOCIATION_ROOT_ROW: usize = 0;
+const COMMITMENT_HASH_ROW: usize = 1;
+
 #[derive(Clone, Debug)]
 pub struct AssociationConfig {
-    pub advice: [Column<Advice>; 3],
-    pub fixed: Column<Fixed>,
+    pub advice: [Column<Advice>; 5],
     pub instance: Column<Instance>,
-    pub s_member: Selector,
+    pub merkle_path: MerklePathConfig,
+    pub poseidon: PoseidonConfig,
 }
 
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+/// The deposit commitment, the secret behind it, and its path in the ASP's
+/// association set: a Poseidon tree of `ASSOCIATION_DEPTH` whose leaves are
+/// the approved commitments. Wiped on drop, like the withdrawal witness.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
 pub struct AssociationWitness {
     pub commitment: [u8; 32],
+    pub secret: [u8; 32],
     pub association_path: Vec<[u8; 32]>,
     pub path_indices: Vec<bool>,
 }
 
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AssociationPublicInputs {
     pub association_root: [u8; 32],
     pub commitment_hash: [u8; 32],
 }
 
+/// What a compliance proof publishes in place of the deposit commitment:
+/// `Poseidon(commitment, secret)`. The commitment is already public as a tree
+/// leaf, so publishing it as is would tie the compliance proof to the deposit.
+/// The circuit hashes the same two cells, so the proof shows the published
+/// hash belongs to a member of the association set.
+pub fn compliance_commitment_hash(commitment: &[u8; 32], secret: &[u8; 32]) -> [u8; 32] {
+    field_to_bytes(poseidon_hash_native(&[
+        field_from_bytes::<Fr>(commitment),
+        field_from_bytes::<Fr>(secret),
+    ]))
+}
+
+impl AssociationWitness {
+    /// Public inputs this witness satisfies: the root its path folds to from
+    /// the commitment, and the commitment hash.
+    pub fn public_inputs(&self) -> AssociationPublicInputs {
+        let zeros = zero_hashes::<Fr>(ASSOCIATION_DEPTH);
+        let siblings: Vec<Fr> = (0..ASSOCIATION_DEPTH)
+            .map(|level| self.association_path.get(level).map(field_from_bytes::<Fr>).unwrap_or(zeros[level]))
+            .collect();
+        let indices: Vec<bool> = (0..ASSOCIATION_DEPTH)
+            .map(|level| self.path_indices.get(level).copied().unwrap_or(false))
+            .collect();
+        let association_root = fold_merkle_path(
+            field_from_bytes::<Fr>(&self.commitment),
+            &siblings,
+            &indices,
+            |left, right| poseidon_hash_native(&[*left, *right]),
+        );
+
+        AssociationPublicInputs {
+            association_root: field_to_bytes(association_root),
+            commitment_hash: compliance_commitment_hash(&self.commitment, &self.secret),
+        }
+    }
+
+    /// Leaf position spelled out by the path bits, little-endian.
+    fn leaf_index(&self) -> u64 {
+        self.path_indices
+            .iter()
+            .take(ASSOCIATION_DEPTH)
+            .enumerate()
+            .filter(|(_, is_right)| **is_right)
+            .fold(0u64, |index, (level, _)| index | (1u64 << level))
+    }
+}
+
+impl AssociationPublicInputs {
+    /// Values of the instance column, in row order: association root,
+    /// commitment hash. Both must be canonical field encodings.
+    pub fn instances(&self) -> Result<Vec<Fr>, CircuitError> {
+        let association_root = field_from_canonical_bytes::<Fr>(&self.association_root)
+            .ok_or_else(|| CircuitError::InvalidInput("Association root is not a canonical field element".into()))?;
+        let commitment_hash = field_from_canonical_bytes::<Fr>(&self.commitment_hash)
+            .ok_or_else(|| CircuitError::InvalidInput("Commitment hash is not a canonical field element".into()))?;
+        Ok(vec![association_root, commitment_hash])
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap_or_default()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CircuitError> {
+        serde_json::from_slice(bytes)
+            .map_err(|e| CircuitError::Serialization(e.to_string()))
+    }
+}
+
+/// Proves that the commitment behind `commitment_hash` is a leaf of the
+/// association set at `association_root`, without revealing which one.
 #[derive(Clone, Debug)]
 pub struct AssociationCircuit<F: PrimeField> {
     pub witness: Option<AssociationWitness>,
@@ -48,13 +134,29 @@ impl<F: PrimeField> Default for AssociationCircuit<F> {
 }
 
 impl<F: PrimeField> AssociationCircuit<F> {
-    pub fn new(witness: AssociationWitness, public_inputs: AssociationPublicInputs) -> Self {
+    /// Paths are resized to exactly `ASSOCIATION_DEPTH`, padding with
+    /// empty-subtree siblings and left indices, so a set built at a smaller
+    /// depth proves against the root it has inside the full-depth tree.
+    pub fn new(mut witness: AssociationWitness, public_inputs: AssociationPublicInputs) -> Self {
+        let zeros = zero_hashes::<F>(ASSOCIATION_DEPTH);
+        while witness.association_path.len() < ASSOCIATION_DEPTH {
+            let level = witness.association_path.len();
+            witness.association_path.push(field_to_bytes(zeros[level]));
+        }
+        witness.association_path.truncate(ASSOCIATION_DEPTH);
+        witness.path_indices.resize(ASSOCIATION_DEPTH, false);
+
         Self {
             witness: Some(witness),
             public_inputs: Some(public_inputs),
             _marker: PhantomData,
         }
     }
+
+    /// Number of values expected in each instance column.
+    pub fn num_instances() -> Vec<usize> {
+        vec![2]
+    }
 }
 
 impl<F: PrimeField> Circuit<F> for AssociationCircuit<F> {
@@ -70,36 +172,27 @@ impl<F: PrimeField> Circuit<F> for AssociationCircuit<F> {
             meta.advice_column(),
             meta.advice_column(),
             meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
         ];
-        
-        let fixed = meta.fixed_column();
+        let constants = meta.fixed_column();
         let instance = meta.instance_column();
-        
+
+        meta.enable_constant(constants);
         meta.enable_equality(instance);
         for col in advice.iter() {
             meta.enable_equality(*col);
         }
 
-        let s_member = meta.selector();
-
-        meta.create_gate("membership_check", |meta| {
-            let s = meta.query_selector(s_member);
-            let current = meta.query_advice(advice[0], Rotation::cur());
-            let sibling = meta.query_advice(advice[1], Rotation::cur());
-            let _parent = meta.query_advice(advice[2], Rotation::cur());
-            
-            let two = Expression::Constant(F::from(2u64));
-            let three = Expression::Constant(F::from(3u64));
-            let _computed = current.clone() * current + sibling.clone() * sibling * two + three;
-            
-            vec![s * Expression::Constant(F::ZERO)]
-        });
+        let merkle_path = MerklePathChip::configure(meta, advice);
+        let round_constants = [meta.fixed_column(), meta.fixed_column(), meta.fixed_column()];
+        let poseidon = PoseidonChip::configure(meta, [advice[0], advice[1], advice[2]], round_constants);
 
         AssociationConfig {
             advice,
-            fixed,
             instance,
-            s_member,
+            merkle_path,
+            poseidon,
         }
     }
 
@@ -108,55 +201,203 @@ impl<F: PrimeField> Circuit<F> for AssociationCircuit<F> {
         config: Self::Config,
         mut layouter: impl Layouter<F>,
     ) -> Result<(), Error> {
-        layouter.assign_region(
-            || "association_proof",
+        let witness = self.witness.as_ref();
+
+        let (commitment, secret, leaf_index) = layouter.assign_region(
+            || "association_inputs",
             |mut region| {
-                let witness = self.witness.as_ref();
-                
-                let _commitment = region.assign_advice(
+                let commitment = region.assign_advice(
                     || "commitment",
                     config.advice[0],
                     0,
-                    || witness.map(|w| bytes_to_field::<F>(&w.commitment)).unwrap_or(Value::unknown()),
+                    || witness.map(|w| Value::known(field_from_bytes::<F>(&w.commitment))).unwrap_or(Value::unknown()),
+                )?;
+                let secret = region.assign_advice(
+                    || "secret",
+                    config.advice[1],
+                    0,
+                    || witness.map(|w| Value::known(field_from_bytes::<F>(&w.secret))).unwrap_or(Value::unknown()),
+                )?;
+                let leaf_index = region.assign_advice(
+                    || "leaf_index",
+                    config.advice[2],
+                    0,
+                    || witness.map(|w| Value::known(F::from(w.leaf_index()))).unwrap_or(Value::unknown()),
                 )?;
-                
-                Ok(())
+
+                Ok((commitment, secret, leaf_index))
             },
         )?;
 
-        Ok(())
-    }
-}
+        let indices: Vec<Value<bool>> = (0..ASSOCIATION_DEPTH)
+            .map(|level| {
+                witness
+                    .map(|w| Value::known(w.path_indices.get(level).copied().unwrap_or(false)))
+                    .unwrap_or(Value::unknown())
+            })
+            .collect();
+        let zeros = zero_hashes::<F>(ASSOCIATION_DEPTH);
+        let siblings: Vec<Value<F>> = (0..ASSOCIATION_DEPTH)
+            .map(|level| {
+                witness
+                    .map(|w| Value::known(w.association_path.get(level).map(field_from_bytes::<F>).unwrap_or(zeros[level])))
+                    .unwrap_or(Value::unknown())
+            })
+            .collect();
+
+        // The index is only there to give the chip its bits; nothing else
+        // reads it, so any member position may be used.
+        let merkle_path = MerklePathChip::<F>::construct(config.merkle_path.clone());
+        let path_bits = merkle_path.path_bits(layouter.namespace(|| "path_index_bits"), &leaf_index, &indices)?;
 
-fn bytes_to_field<F: PrimeField>(bytes: &[u8; 32]) -> Value<F> {
-    let mut acc = F::ZERO;
-    let base = F::from(256u64);
-    for byte in bytes.iter().take(31) {
-        acc = acc * base + F::from(*byte as u64);
+        let poseidon = PoseidonChip::<F>::construct(config.poseidon.clone());
+        let commitment_hash = poseidon.hash(layouter.namespace(|| "commitment_hash"), &[commitment.clone(), secret])?;
+        let root = merkle_path.root(layouter.namespace(|| "association_path"), &poseidon, commitment, &path_bits, &siblings)?;
+
+        layouter.constrain_instance(root.cell(), config.instance, ASSOCIATION_ROOT_ROW)?;
+        layouter.constrain_instance(commitment_hash.cell(), config.instance, COMMITMENT_HASH_ROW)?;
+
+        Ok(())
     }
-    Value::known(acc)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use halo2_proofs::{
-        dev::MockProver,
-        halo2curves::bn256::Fr,
-    };
+    use halo2_proofs::dev::MockProver;
+    use crate::merkle::PoseidonMerkleTree;
+
+    const ASSOCIATION_TEST_K: u32 = 10;
+
+    /// An association set approving three commitments, and the witness for
+    /// the one at leaf 2.
+    fn member_witness() -> (PoseidonMerkleTree, AssociationWitness) {
+        let commitment = [9u8; 32];
+        let mut tree = PoseidonMerkleTree::empty(ASSOCIATION_DEPTH);
+        tree.insert_batch(0, &[[1u8; 32], [2u8; 32], commitment]).unwrap();
+
+        let (association_path, path_indices) = tree.generate_proof_for_leaf(&commitment, 2).unwrap();
+        let witness = AssociationWitness {
+            commitment,
+            secret: [4u8; 32],
+            association_path,
+            path_indices,
+        };
+        (tree, witness)
+    }
+
+    fn mock_prover(witness: AssociationWitness, instances: Vec<Fr>) -> MockProver<Fr> {
+        let public_inputs = witness.public_inputs();
+        let circuit = AssociationCircuit::<Fr>::new(witness, public_inputs);
+        MockProver::run(ASSOCIATION_TEST_K, &circuit, vec![instances]).unwrap()
+    }
 
     #[test]
     fn test_association_circuit() {
+        let (tree, witness) = member_witness();
+        let public_inputs = witness.public_inputs();
+        assert_eq!(public_inputs.association_root, tree.root());
+        assert_eq!(public_inputs.commitment_hash, compliance_commitment_hash(&[9u8; 32], &[4u8; 32]));
+
+        mock_prover(witness, public_inputs.instances().unwrap()).verify().unwrap();
+    }
+
+    #[test]
+    fn test_rejects_non_member() {
+        let (tree, member) = member_witness();
+
+        // Same path, but a commitment the set never approved.
+        let mut outsider = member.clone();
+        outsider.commitment = [8u8; 32];
+        let mut instances = outsider.public_inputs().instances().unwrap();
+        instances[ASSOCIATION_ROOT_ROW] = field_from_canonical_bytes::<Fr>(&tree.root()).unwrap();
+        assert!(mock_prover(outsider, instances).verify().is_err());
+    }
+
+    #[test]
+    fn test_rejects_commitment_hash_of_another_secret() {
+        let (_, witness) = member_witness();
+        let mut instances = witness.public_inputs().instances().unwrap();
+        instances[COMMITMENT_HASH_ROW] = poseidon_hash_native(&[
+            field_from_bytes::<Fr>(&[9u8; 32]),
+            field_from_bytes::<Fr>(&[5u8; 32]),
+        ]);
+        assert!(mock_prover(witness, instances).verify().is_err());
+    }
+
+    #[test]
+    fn test_path_matches_full_depth_tree() {
+        let commitment = [7u8; 32];
+        let mut tree = PoseidonMerkleTree::empty(ASSOCIATION_DEPTH);
+        tree.insert(0, [1u8; 32]).unwrap();
+        tree.insert(777, commitment).unwrap();
+
+        let (association_path, path_indices) = tree.generate_proof_for_leaf(&commitment, 777).unwrap();
         let witness = AssociationWitness {
-            commitment: [1u8; 32],
-            association_path: vec![[0u8; 32]; ASSOCIATION_DEPTH],
-            path_indices: vec![false; ASSOCIATION_DEPTH],
+            commitment,
+            secret: [2u8; 32],
+            association_path,
+            path_indices,
         };
-        
-        let public_inputs = AssociationPublicInputs::default();
-        
-        let circuit = AssociationCircuit::<Fr>::new(witness, public_inputs);
-        let prover = MockProver::run(8, &circuit, vec![vec![]]).unwrap();
+        let instances = witness.public_inputs().instances().unwrap();
+        assert_eq!(field_to_bytes(instances[ASSOCIATION_ROOT_ROW]), tree.root());
+        mock_prover(witness.clone(), instances.clone()).verify().unwrap();
+
+        let mut tampered = witness;
+        tampered.association_path[3][0] ^= 1;
+        assert!(mock_prover(tampered, instances).verify().is_err());
+    }
+
+    #[test]
+    fn test_short_path_is_padded() {
+        let mut tree = PoseidonMerkleTree::empty(4);
+        tree.insert_batch(0, &[[5u8; 32], [3u8; 32]]).unwrap();
+        let (association_path, path_indices) = tree.generate_proof_for_leaf(&[3u8; 32], 1).unwrap();
+        let witness = AssociationWitness {
+            commitment: [3u8; 32],
+            secret: [6u8; 32],
+            association_path,
+            path_indices,
+        };
+        let public_inputs = witness.public_inputs();
+
+        let circuit = AssociationCircuit::<Fr>::new(witness, public_inputs.clone());
+        let padded = circuit.witness.as_ref().unwrap();
+        assert_eq!(padded.association_path.len(), ASSOCIATION_DEPTH);
+        assert_eq!(padded.path_indices.len(), ASSOCIATION_DEPTH);
+        assert_eq!(padded.association_path[4], field_to_bytes(zero_hashes::<Fr>(ASSOCIATION_DEPTH)[4]));
+        assert!(!padded.path_indices[4]);
+        assert_eq!(padded.public_inputs(), public_inputs);
+
+        let instances = public_inputs.instances().unwrap();
+        let prover = MockProver::run(ASSOCIATION_TEST_K, &circuit, vec![instances.clone()]).unwrap();
         prover.verify().unwrap();
+
+        let (prover_params, verifier_params) =
+            crate::setup_circuit(ASSOCIATION_TEST_K, &AssociationCircuit::<Fr>::default()).unwrap();
+        let proof = crate::prove_circuit(&prover_params, circuit, &[&instances]).unwrap();
+        assert!(crate::verify_circuit_proof(&verifier_params, &proof, &[&instances]).unwrap());
+    }
+
+    #[test]
+    fn test_public_inputs_roundtrip() {
+        let (_, witness) = member_witness();
+        let public_inputs = witness.public_inputs();
+        let instances = public_inputs.instances().unwrap();
+
+        let (prover_params, verifier_params) =
+            crate::setup_circuit(ASSOCIATION_TEST_K, &AssociationCircuit::<Fr>::default()).unwrap();
+        let circuit = AssociationCircuit::<Fr>::new(witness, public_inputs.clone());
+        let proof = crate::prove_circuit(&prover_params, circuit, &[&instances]).unwrap();
+
+        let decoded = AssociationPublicInputs::from_bytes(&public_inputs.to_bytes()).unwrap();
+        assert_eq!(decoded, public_inputs);
+        let decoded_instances = decoded.instances().unwrap();
+        assert!(crate::verify_circuit_proof(&verifier_params, &proof, &[&decoded_instances]).unwrap());
+    }
+
+    #[test]
+    fn test_public_inputs_rejects_malformed_bytes() {
+        assert!(AssociationPublicInputs::from_bytes(b"not json").is_err());
     }
 }
@@ -0,0 +1,330 @@
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::{create_proof, keygen_vk, verify_proof, Circuit, ProvingKey, VerifyingKey},
+    poly::kzg::{
+        commitment::{KZGCommitmentScheme, ParamsKZG},
+        multiopen::{ProverSHPLONK, VerifierSHPLONK},
+        strategy::{AccumulatorStrategy, SingleStrategy},
+    },
+    poly::VerificationStrategy,
+    transcript::{Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer},
+};
+use rand::rngs::OsRng;
+
+use crate::CircuitError;
+
+/// A proof system that can prove circuits of type `C` and check the result.
+/// `public_inputs` is one slice per instance column.
+pub trait ProvingBackend<C> {
+    fn prove(&self, circuit: C, public_inputs: &[&[Fr]]) -> Result<Vec<u8>, CircuitError>;
+    fn verify(&self, proof: &[u8], public_inputs: &[&[Fr]]) -> Result<bool, CircuitError>;
+}
+
+/// Halo2 over KZG/BN256 with SHPLONK openings and a Blake2b transcript.
+pub struct Halo2Backend<'a> {
+    params: &'a ParamsKZG<Bn256>,
+    vk: &'a VerifyingKey<G1Affine>,
+    pk: Option<&'a ProvingKey<G1Affine>>,
+}
+
+impl<'a> Halo2Backend<'a> {
+    pub fn prover(params: &'a ParamsKZG<Bn256>, pk: &'a ProvingKey<G1Affine>) -> Self {
+        Self {
+            params,
+            vk: pk.get_vk(),
+            pk: Some(pk),
+        }
+    }
+
+    pub fn verifier(params: &'a ParamsKZG<Bn256>, vk: &'a VerifyingKey<G1Affine>) -> Self {
+        Self { params, vk, pk: None }
+    }
+
+    /// Verification only depends on the keys, not on the circuit type.
+    pub fn verify(&self, proof: &[u8], public_inputs: &[&[Fr]]) -> Result<bool, CircuitError> {
+        let mut transcript = Blake2bRead::<_, G1Affine, Challenge255<_>>::init(proof);
+
+        let strategy = SingleStrategy::new(self.params);
+
+        verify_proof::<KZGCommitmentScheme<Bn256>, VerifierSHPLONK<'_, Bn256>, _, _, _>(
+            self.params,
+            self.vk,
+            strategy,
+            &[public_inputs],
+            &mut transcript,
+        )
+        .map_err(|e| CircuitError::ProofVerification(format!("Verification failed: {:?}", e)))?;
+
+        Ok(true)
+    }
+
+    /// Checks several proofs against the same key, folding their pairing
+    /// checks into one accumulator. Only when the combined check fails are the
+    /// proofs re-verified one by one to find the bad ones.
+    pub fn verify_batch(&self, proofs: &[(&[u8], &[&[Fr]])]) -> Vec<bool> {
+        let mut results = vec![true; proofs.len()];
+        let mut strategy = AccumulatorStrategy::new(self.params);
+
+        for (result, (proof, public_inputs)) in results.iter_mut().zip(proofs) {
+            let mut transcript = Blake2bRead::<_, G1Affine, Challenge255<_>>::init(*proof);
+            match verify_proof::<KZGCommitmentScheme<Bn256>, VerifierSHPLONK<'_, Bn256>, _, _, _>(
+                self.params,
+                self.vk,
+                strategy.clone(),
+                &[*public_inputs],
+                &mut transcript,
+            ) {
+                Ok(next) => strategy = next,
+                Err(_) => *result = false,
+            }
+        }
+
+        if !strategy.finalize() {
+            for (result, (proof, public_inputs)) in results.iter_mut().zip(proofs) {
+                if *result {
+                    *result = self.verify(proof, public_inputs).is_ok();
+                }
+            }
+        }
+
+        results
+    }
+}
+
+/// Owns the params and a verifying key derived once, for checking many proofs
+/// against the same circuit. Nothing is rebuilt per proof: `verify` and
+/// `verify_batch` borrow the cached params and key, and the key's fixed
+/// commitments and transcript hash were computed when it was generated.
+/// `verify_batch` also folds the pairing checks of all its proofs into one.
+pub struct CachedVerifier {
+    params: ParamsKZG<Bn256>,
+    vk: VerifyingKey<G1Affine>,
+}
+
+impl CachedVerifier {
+    pub fn new(params: ParamsKZG<Bn256>, vk: VerifyingKey<G1Affine>) -> Self {
+        Self { params, vk }
+    }
+
+    /// Derives the verifying key for `C` from `params`. This is the only
+    /// place a `CachedVerifier` runs `keygen_vk`.
+    pub fn for_circuit<C: Circuit<Fr> + Default>(params: ParamsKZG<Bn256>) -> Result<Self, CircuitError> {
+        let vk = keygen_vk(&params, &C::default())
+            .map_err(|e| CircuitError::ProofVerification(format!("VK generation failed: {:?}", e)))?;
+        Ok(Self::new(params, vk))
+    }
+
+    pub fn params(&self) -> &ParamsKZG<Bn256> {
+        &self.params
+    }
+
+    pub fn vk(&self) -> &VerifyingKey<G1Affine> {
+        &self.vk
+    }
+
+    pub fn verify(&self, proof: &[u8], public_inputs: &[&[Fr]]) -> bool {
+        Halo2Backend::verifier(&self.params, &self.vk)
+            .verify(proof, public_inputs)
+            .is_ok()
+    }
+
+    /// See `Halo2Backend::verify_batch`.
+    pub fn verify_batch(&self, proofs: &[(&[u8], &[&[Fr]])]) -> Vec<bool> {
+        Halo2Backend::verifier(&self.params, &self.vk).verify_batch(proofs)
+    }
+}
+
+impl From<crate::VerifierParams> for CachedVerifier {
+    fn from(verifier: crate::VerifierParams) -> Self {
+        Self::new(verifier.params, verifier.vk)
+    }
+}
+
+impl<C: Circuit<Fr>> ProvingBackend<C> for Halo2Backend<'_> {
+    fn prove(&self, circuit: C, public_inputs: &[&[Fr]]) -> Result<Vec<u8>, CircuitError> {
+        let pk = self.pk.ok_or_else(|| {
+            CircuitError::ProofGeneration("Backend was created without a proving key".into())
+        })?;
+
+        let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
+
+        create_proof::<KZGCommitmentScheme<Bn256>, ProverSHPLONK<'_, Bn256>, _, _, _, _>(
+            self.params,
+            pk,
+            &[circuit],
+            &[public_inputs],
+            OsRng,
+            &mut transcript,
+        )
+        .map_err(|e| CircuitError::ProofGeneration(format!("Proof creation failed: {:?}", e)))?;
+
+        Ok(transcript.finalize())
+    }
+
+    fn verify(&self, proof: &[u8], public_inputs: &[&[Fr]]) -> Result<bool, CircuitError> {
+        Halo2Backend::verify(self, proof, public_inputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use halo2_proofs::{circuit::Layouter, plonk::{ConstraintSystem, Error}};
+    use crate::withdrawal_circuit::{WithdrawalCircuit, WithdrawalWitness, MERKLE_DEPTH};
+
+    #[derive(Default)]
+    struct MockBackend {
+        proved: Cell<usize>,
+        verified: Cell<usize>,
+    }
+
+    impl ProvingBackend<WithdrawalCircuit<Fr>> for MockBackend {
+        fn prove(&self, _circuit: WithdrawalCircuit<Fr>, public_inputs: &[&[Fr]]) -> Result<Vec<u8>, CircuitError> {
+            self.proved.set(self.proved.get() + 1);
+            Ok(vec![public_inputs.len() as u8, 0xaa])
+        }
+
+        fn verify(&self, proof: &[u8], public_inputs: &[&[Fr]]) -> Result<bool, CircuitError> {
+            self.verified.set(self.verified.get() + 1);
+            Ok(proof == [public_inputs.len() as u8, 0xaa])
+        }
+    }
+
+    #[test]
+    fn test_mock_backend_dispatch() {
+        let witness = WithdrawalWitness {
+            merkle_path: vec![[0u8; 32]; MERKLE_DEPTH],
+            path_indices: vec![false; MERKLE_DEPTH],
+            ..Default::default()
+        };
+        let public_inputs = witness.public_inputs::<Fr>([1u8; 20]);
+        let instances = public_inputs.instances::<Fr>().unwrap();
+        let circuit = WithdrawalCircuit::<Fr>::new(witness, public_inputs);
+
+        let backend = MockBackend::default();
+        let proof = crate::prove_withdrawal_with(&backend, circuit, &[&instances]).unwrap();
+        assert_eq!(proof.bytes, vec![1, 0xaa]);
+        assert!(crate::verify_withdrawal_with(&backend, &proof, &[&instances]).unwrap());
+
+        assert_eq!(backend.proved.get(), 1);
+        assert_eq!(backend.verified.get(), 1);
+    }
+
+    #[test]
+    fn test_mismatched_recorded_inputs_skip_backend() {
+        let witness = WithdrawalWitness {
+            merkle_path: vec![[0u8; 32]; MERKLE_DEPTH],
+            path_indices: vec![false; MERKLE_DEPTH],
+            ..Default::default()
+        };
+        let public_inputs = witness.public_inputs::<Fr>([1u8; 20]);
+        let instances = public_inputs.instances::<Fr>().unwrap();
+        let circuit = WithdrawalCircuit::<Fr>::new(witness, public_inputs);
+
+        let backend = MockBackend::default();
+        let mut proof = crate::prove_withdrawal_with(&backend, circuit, &[&instances]).unwrap();
+
+        assert!(crate::verify_withdrawal_with(&backend, &proof, &[&instances]).unwrap());
+        assert_eq!(backend.verified.get(), 1);
+
+        proof.public_inputs.push(crate::encoding::SerField(Fr::from(1u64)));
+        assert!(!crate::verify_withdrawal_with(&backend, &proof, &[&instances]).unwrap());
+        assert_eq!(backend.verified.get(), 1);
+    }
+
+    #[test]
+    fn test_verify_batch_flags_bad_proof() {
+        let (prover, verifier) = crate::setup_withdrawal_circuit(11).unwrap();
+
+        let mut proofs = Vec::new();
+        for i in 0..4u8 {
+            let witness = WithdrawalWitness {
+                secret: [i; 32],
+                merkle_path: vec![[0u8; 32]; MERKLE_DEPTH],
+                path_indices: vec![false; MERKLE_DEPTH],
+                ..Default::default()
+            };
+            let public_inputs = witness.public_inputs::<Fr>([i; 20]);
+            let instances = public_inputs.instances::<Fr>().unwrap();
+            let circuit = WithdrawalCircuit::<Fr>::new(witness, public_inputs);
+            let bytes = crate::prove_withdrawal(&prover, circuit, &[&instances]).unwrap().bytes;
+            proofs.push((bytes, instances));
+        }
+
+        let backend = verifier.backend();
+        let columns: Vec<[&[Fr]; 1]> = proofs.iter().map(|(_, instances)| [instances.as_slice()]).collect();
+        let batch: Vec<(&[u8], &[&[Fr]])> = proofs.iter().zip(&columns).map(|((p, _), c)| (&p[..], &c[..])).collect();
+        assert_eq!(backend.verify_batch(&batch), vec![true; 4]);
+
+        let last = proofs[2].0.len() - 1;
+        proofs[2].0[last] ^= 1;
+        let batch: Vec<(&[u8], &[&[Fr]])> = proofs.iter().zip(&columns).map(|((p, _), c)| (&p[..], &c[..])).collect();
+        assert_eq!(backend.verify_batch(&batch), vec![true, true, false, true]);
+    }
+
+    /// The withdrawal circuit, counting how often its layout is synthesized.
+    /// `keygen_vk` synthesizes the circuit; verifying a proof does not.
+    #[derive(Clone, Default)]
+    struct CountingCircuit(WithdrawalCircuit<Fr>);
+
+    static SYNTHESIZED: AtomicUsize = AtomicUsize::new(0);
+
+    impl Circuit<Fr> for CountingCircuit {
+        type Config = <WithdrawalCircuit<Fr> as Circuit<Fr>>::Config;
+        type FloorPlanner = <WithdrawalCircuit<Fr> as Circuit<Fr>>::FloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self(self.0.without_witnesses())
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            WithdrawalCircuit::<Fr>::configure(meta)
+        }
+
+        fn synthesize(&self, config: Self::Config, layouter: impl Layouter<Fr>) -> Result<(), Error> {
+            SYNTHESIZED.fetch_add(1, Ordering::SeqCst);
+            self.0.synthesize(config, layouter)
+        }
+    }
+
+    #[test]
+    fn test_cached_verifier_derives_vk_once() {
+        let (prover, verifier) = crate::setup_withdrawal_circuit(11).unwrap();
+
+        let proofs: Vec<(Vec<u8>, Vec<Fr>)> = (0..4u8)
+            .map(|i| {
+                let witness = WithdrawalWitness {
+                    nullifier_seed: [i; 32],
+                    merkle_path: vec![[0u8; 32]; MERKLE_DEPTH],
+                    path_indices: vec![false; MERKLE_DEPTH],
+                    ..Default::default()
+                };
+                let public_inputs = witness.public_inputs::<Fr>([i; 20]);
+                let instances = public_inputs.instances::<Fr>().unwrap();
+                let circuit = WithdrawalCircuit::<Fr>::new(witness, public_inputs);
+                (crate::prove_withdrawal(&prover, circuit, &[&instances]).unwrap().bytes, instances)
+            })
+            .collect();
+
+        let before = SYNTHESIZED.load(Ordering::SeqCst);
+        let cached = CachedVerifier::for_circuit::<CountingCircuit>(verifier.params.clone()).unwrap();
+        let after_keygen = SYNTHESIZED.load(Ordering::SeqCst);
+        assert!(after_keygen > before);
+        assert_eq!(cached.vk().transcript_repr(), verifier.vk.transcript_repr());
+
+        for (proof, instances) in proofs.iter().cycle().take(32) {
+            assert!(cached.verify(proof, &[instances]));
+        }
+        let columns: Vec<[&[Fr]; 1]> = proofs.iter().map(|(_, instances)| [instances.as_slice()]).collect();
+        let batch: Vec<(&[u8], &[&[Fr]])> = proofs.iter().zip(&columns).map(|((p, _), c)| (&p[..], &c[..])).collect();
+        assert_eq!(cached.verify_batch(&batch), vec![true; 4]);
+        assert_eq!(SYNTHESIZED.load(Ordering::SeqCst), after_keygen);
+
+        // A verifier built from existing keys never synthesizes at all.
+        let from_keys = CachedVerifier::from(verifier);
+        assert!(from_keys.verify(&proofs[0].0, &[&proofs[0].1]));
+        assert_eq!(SYNTHESIZED.load(Ordering::SeqCst), after_keygen);
+    }
+}
@@ -1,62 +1,98 @@
+use std::env;
 use std::fs::File;
-use std::io::Write;
+use std::process;
 use halo2_proofs::{
     halo2curves::bn256::{Bn256, Fr},
-    plonk::{keygen_pk, keygen_vk, ProvingKey, VerifyingKey},
-    poly::{
-        commitment::Params,
-        kzg::{
-            commitment::{KZGCommitmentScheme, ParamsKZG},
-        },
-    },
-    SerdeFormat,
+    poly::kzg::commitment::ParamsKZG,
 };
 use zkenclave_circuits::{
+    association_circuit::{AssociationCircuit, AssociationPublicInputs, AssociationWitness},
+    keygen::{check_memory_cap, peak_memory_kib, read_srs, write_circuit_keys, write_params},
     withdrawal_circuit::{WithdrawalCircuit, WithdrawalPublicInputs, WithdrawalWitness},
+    CircuitError,
 };
 use rand::rngs::OsRng;
 
-fn main() {
-    let k = 13;
-    
-    println!("1. Generating Params for K={}...", k);
-    let params = ParamsKZG::<Bn256>::setup(k, OsRng);
-    
-    let mut params_file = File::create("src/params.bin").unwrap();
-    params.write(&mut params_file).unwrap();
+const USAGE: &str = "usage: setup [--k <K>] [--srs <srs.bin>] [--max-memory-mib <MiB>]";
+
+struct Args {
+    k: u32,
+    srs: Option<String>,
+    max_memory_mib: Option<u64>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut k = 13;
+    let mut srs = None;
+    let mut max_memory_mib = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or_else(|| format!("missing value for {}", flag))?;
+        match flag.as_str() {
+            "--k" => k = value.parse().map_err(|_| format!("invalid K {}", value))?,
+            "--srs" => srs = Some(value),
+            "--max-memory-mib" => {
+                max_memory_mib = Some(value.parse().map_err(|_| format!("invalid memory cap {}", value))?)
+            }
+            other => return Err(format!("unknown argument {}", other)),
+        }
+    }
+
+    Ok(Args { k, srs, max_memory_mib })
+}
+
+fn create(path: &str) -> Result<File, CircuitError> {
+    File::create(path).map_err(|e| CircuitError::Serialization(format!("cannot create {}: {}", path, e)))
+}
+
+fn run(args: Args) -> Result<(), CircuitError> {
+    let params = match &args.srs {
+        Some(path) => {
+            println!("1. Reading SRS from {} for K={}...", path, args.k);
+            let file = File::open(path)
+                .map_err(|e| CircuitError::Serialization(format!("cannot open {}: {}", path, e)))?;
+            read_srs(file, args.k)?
+        }
+        None => {
+            println!("1. Generating Params for K={}...", args.k);
+            ParamsKZG::<Bn256>::setup(args.k, OsRng)
+        }
+    };
+    check_memory_cap(args.max_memory_mib, "params")?;
+
+    write_params(&params, create("src/params.bin")?)?;
     println!("   Saved src/params.bin");
 
     println!("2. Generating Keys...");
-    let witness = WithdrawalWitness::default(); 
-    let public_inputs = WithdrawalPublicInputs::default();
-    let circuit = WithdrawalCircuit::<Fr>::new(witness, public_inputs);
-    
-    let vk = keygen_vk(&params, &circuit).expect("keygen_vk failed");
-    let pk = keygen_pk(&params, vk.clone(), &circuit).expect("keygen_pk failed");
-
-    let mut vk_file = File::create("src/withdrawal_vk.bin").unwrap();
-    vk.write(&mut vk_file, SerdeFormat::RawBytes).unwrap();
-    
-    let mut pk_file = File::create("src/withdrawal_pk.bin").unwrap();
-    pk.write(&mut pk_file, SerdeFormat::RawBytes).unwrap();
-    
+    let circuit = WithdrawalCircuit::<Fr>::new(WithdrawalWitness::default(), WithdrawalPublicInputs::default());
+    write_circuit_keys(&params, &circuit, create("src/withdrawal_vk.bin")?, create("src/withdrawal_pk.bin")?, args.max_memory_mib)?;
     println!("   Saved src/withdrawal_vk.bin and src/withdrawal_pk.bin");
 
     println!("2b. Generating Association Keys...");
-    let assoc_witness = zkenclave_circuits::association_circuit::AssociationWitness::default();
-    let assoc_pub = zkenclave_circuits::association_circuit::AssociationPublicInputs::default();
-    let assoc_circuit = zkenclave_circuits::association_circuit::AssociationCircuit::<Fr>::new(assoc_witness, assoc_pub);
+    let assoc_circuit = AssociationCircuit::<Fr>::new(AssociationWitness::default(), AssociationPublicInputs::default());
+    write_circuit_keys(&params, &assoc_circuit, create("src/association_vk.bin")?, create("src/association_pk.bin")?, args.max_memory_mib)?;
+    println!("   Saved src/association_vk.bin and src/association_pk.bin");
 
-    let assoc_vk = keygen_vk(&params, &assoc_circuit).expect("assoc keygen_vk failed");
-    let assoc_pk = keygen_pk(&params, assoc_vk.clone(), &assoc_circuit).expect("assoc keygen_pk failed");
+    println!("3. Generating Solidity Verifier (Skipped - requires template)...");
+    match peak_memory_kib() {
+        Some(kib) => println!("Done! Peak memory: {} MiB", kib / 1024),
+        None => println!("Done!"),
+    }
+    Ok(())
+}
 
-    let mut assoc_vk_file = File::create("src/association_vk.bin").unwrap();
-    assoc_vk.write(&mut assoc_vk_file, SerdeFormat::RawBytes).unwrap();
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
 
-    let mut assoc_pk_file = File::create("src/association_pk.bin").unwrap();
-    assoc_pk.write(&mut assoc_pk_file, SerdeFormat::RawBytes).unwrap();
-    println!("   Saved src/association_vk.bin and src/association_pk.bin");
-    
-    println!("3. Generating Solidity Verifier (Skipped - requires template)...");
-    println!("Done!");
+    if let Err(e) = run(args) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
 }
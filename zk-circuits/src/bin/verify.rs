@@ -0,0 +1,133 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::process;
+use std::time::Instant;
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr},
+    poly::{commitment::Params, kzg::commitment::ParamsKZG},
+};
+use zkenclave_circuits::{
+    keygen::read_circuit_vk,
+    verify_withdrawal,
+    withdrawal_circuit::{WithdrawalCircuit, WithdrawalPublicInputs},
+    Proof, VerifierParams,
+};
+
+const USAGE: &str =
+    "usage: verify --params <params.bin> --vk <withdrawal_vk.bin> --public-inputs <inputs.json> [--proof <proof.json|->]";
+
+struct Args {
+    params: String,
+    vk: String,
+    public_inputs: String,
+    proof: String,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut params = None;
+    let mut vk = None;
+    let mut public_inputs = None;
+    let mut proof = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or_else(|| format!("missing value for {}", flag))?;
+        match flag.as_str() {
+            "--params" => params = Some(value),
+            "--vk" => vk = Some(value),
+            "--public-inputs" => public_inputs = Some(value),
+            "--proof" => proof = Some(value),
+            other => return Err(format!("unknown argument {}", other)),
+        }
+    }
+
+    Ok(Args {
+        params: params.ok_or("--params is required")?,
+        vk: vk.ok_or("--vk is required")?,
+        public_inputs: public_inputs.ok_or("--public-inputs is required")?,
+        proof: proof.unwrap_or_else(|| "-".to_string()),
+    })
+}
+
+fn load_verifier(params_path: &str, vk_path: &str) -> Result<VerifierParams, String> {
+    let mut params_reader = BufReader::new(
+        File::open(params_path).map_err(|e| format!("cannot open {}: {}", params_path, e))?,
+    );
+    let params = ParamsKZG::<Bn256>::read(&mut params_reader)
+        .map_err(|e| format!("cannot read params: {}", e))?;
+
+    let vk_file = File::open(vk_path).map_err(|e| format!("cannot open {}: {}", vk_path, e))?;
+    let vk = read_circuit_vk::<WithdrawalCircuit<Fr>, _>(vk_file)
+        .map_err(|e| format!("cannot read vk: {}", e))?;
+
+    Ok(VerifierParams { params, vk })
+}
+
+/// The statement being checked, given separately from the proof: a JSON
+/// `WithdrawalPublicInputs` with the root, nullifier, recipient, amount, vault
+/// and chain id the caller expects. A proof only passes if it was made for
+/// exactly these; the inputs recorded in the proof file are not trusted.
+fn load_public_inputs(path: &str) -> Result<Vec<Fr>, String> {
+    let bytes = fs::read(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    let public_inputs: WithdrawalPublicInputs =
+        serde_json::from_slice(&bytes).map_err(|e| format!("cannot parse {}: {}", path, e))?;
+    public_inputs.instances::<Fr>().map_err(|e| e.to_string())
+}
+
+fn load_proof(path: &str) -> Result<Proof, String> {
+    let mut bytes = Vec::new();
+    if path == "-" {
+        io::stdin().read_to_end(&mut bytes).map_err(|e| format!("cannot read stdin: {}", e))?;
+    } else {
+        File::open(path)
+            .and_then(|mut f| f.read_to_end(&mut bytes))
+            .map_err(|e| format!("cannot read {}: {}", path, e))?;
+    }
+
+    Proof::from_bytes(&bytes).map_err(|e| e.to_string())
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let started = Instant::now();
+    let loaded = load_verifier(&args.params, &args.vk).and_then(|verifier| {
+        let instances = load_public_inputs(&args.public_inputs)?;
+        let proof = load_proof(&args.proof)?;
+        Ok((verifier, proof, instances))
+    });
+    let (verifier, proof, instances) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(2);
+        }
+    };
+    let load_time = started.elapsed();
+
+    let started = Instant::now();
+    let result = verify_withdrawal(&verifier, &proof, &[&instances]);
+    let verify_time = started.elapsed();
+
+    println!("Loaded params and vk in {:?}", load_time);
+    match result {
+        Ok(true) => {
+            println!("PASS: proof verified in {:?}", verify_time);
+        }
+        Ok(false) => {
+            println!("FAIL: proof rejected in {:?}", verify_time);
+            process::exit(1);
+        }
+        Err(e) => {
+            println!("FAIL: {} ({:?})", e, verify_time);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use ff::Field;
+use halo2_proofs::halo2curves::bn256::Fr;
+use rand::{rngs::OsRng, RngCore};
+use serde::{Serialize, Deserialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::encoding::{field_from_bytes, field_to_bytes};
+use crate::withdrawal_circuit::compute_commitment;
+
+#[derive(Clone, Debug, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct Deposit {
+    pub secret: [u8; 32],
+    pub nullifier_seed: [u8; 32],
+    /// Canonical (little-endian) encoding of the commitment field element.
+    pub commitment: [u8; 32],
+}
+
+pub fn generate_deposit() -> Deposit {
+    let secret = random_secret(&mut OsRng);
+    let nullifier_seed = random_secret(&mut OsRng);
+    let commitment = commitment_bytes(&secret, &nullifier_seed);
+
+    Deposit {
+        secret,
+        nullifier_seed,
+        commitment,
+    }
+}
+
+pub fn commitment_bytes(secret: &[u8; 32], nullifier_seed: &[u8; 32]) -> [u8; 32] {
+    let commitment = compute_commitment(
+        field_from_bytes::<Fr>(secret),
+        field_from_bytes::<Fr>(nullifier_seed),
+    );
+    field_to_bytes(commitment)
+}
+
+/// Uniform over the whole scalar field, written in its canonical encoding so
+/// the circuit reads back exactly the sampled element.
+fn random_secret(rng: &mut impl RngCore) -> [u8; 32] {
+    field_to_bytes(Fr::random(rng))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::dev::MockProver;
+    use crate::encoding::field_from_canonical_bytes;
+    use crate::withdrawal_circuit::{
+        WithdrawalCircuit, WithdrawalPublicInputs, WithdrawalWitness, MERKLE_DEPTH,
+    };
+
+    #[test]
+    fn test_generated_secrets_in_range() {
+        for _ in 0..32 {
+            let deposit = generate_deposit();
+            assert!(field_from_canonical_bytes::<Fr>(&deposit.secret).is_some());
+            assert!(field_from_canonical_bytes::<Fr>(&deposit.nullifier_seed).is_some());
+            assert_ne!(deposit.secret, deposit.nullifier_seed);
+        }
+    }
+
+    #[test]
+    fn test_deposit_secrets_are_zeroized() {
+        fn assert_zeroize_on_drop<T: ZeroizeOnDrop>() {}
+        assert_zeroize_on_drop::<Deposit>();
+        assert_zeroize_on_drop::<crate::link_circuit::LinkWitness>();
+
+        let mut deposit = generate_deposit();
+        deposit.zeroize();
+        assert_eq!(deposit.secret, [0u8; 32]);
+        assert_eq!(deposit.nullifier_seed, [0u8; 32]);
+    }
+
+    #[test]
+    fn test_commitment_matches_circuit() {
+        let deposit = generate_deposit();
+        assert_eq!(deposit.commitment, commitment_bytes(&deposit.secret, &deposit.nullifier_seed));
+        assert_eq!(
+            deposit.commitment,
+            field_to_bytes(crate::poseidon::poseidon_hash_native(&[
+                field_from_bytes::<Fr>(&deposit.secret),
+                field_from_bytes::<Fr>(&deposit.nullifier_seed),
+            ]))
+        );
+
+        let witness = WithdrawalWitness {
+            secret: deposit.secret,
+            nullifier_seed: deposit.nullifier_seed,
+            amount: 1,
+            leaf_index: 0,
+            merkle_path: vec![[0u8; 32]; MERKLE_DEPTH],
+            path_indices: vec![false; MERKLE_DEPTH],
+        };
+
+        let public_inputs = witness.public_inputs::<Fr>([0xab; 20]);
+        let instances = public_inputs.instances::<Fr>().unwrap();
+        let circuit = WithdrawalCircuit::<Fr>::new(witness, public_inputs);
+        let prover = MockProver::run(11, &circuit, vec![instances]).unwrap();
+        prover.verify().unwrap();
+    }
+}
@@ -0,0 +1,372 @@
+use std::marker::PhantomData;
+use ff::PrimeField;
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    halo2curves::bn256::Fr,
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Instance},
+};
+use ff::Field;
+use rand::rngs::OsRng;
+use serde::{Serialize, Deserialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::encoding::{field_from_bytes, field_from_canonical_bytes, field_to_bytes, u64_to_bytes};
+use crate::merkle::fold_merkle_path;
+use crate::merkle_path::{MerklePathChip, MerklePathConfig};
+use crate::poseidon::{poseidon_hash_native, PoseidonChip, PoseidonConfig};
+use crate::range_check::{RangeCheckChip, RangeCheckConfig, AMOUNT_BITS};
+use crate::withdrawal_circuit::{compute_nullifier, zero_hashes, MERKLE_DEPTH};
+use crate::CircuitError;
+
+const NULLIFIER_ROW: usize = 0;
+const AMOUNT_COMMITMENT_ROW: usize = 1;
+const MERKLE_ROOT_ROW: usize = 2;
+
+#[derive(Clone, Debug)]
+pub struct ConfidentialConfig {
+    pub advice: [Column<Advice>; 5],
+    pub instance: Column<Instance>,
+    pub merkle_path: MerklePathConfig,
+    pub poseidon: PoseidonConfig,
+    pub range_check: RangeCheckConfig,
+}
+
+/// Private inputs. `amount` is the little-endian field encoding of the value,
+/// so the circuit (not the type) is what limits it to `AMOUNT_BITS`. The path
+/// leads from the deposit's `confidential_leaf` to the pool root.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct ConfidentialWitness {
+    pub nullifier_seed: [u8; 32],
+    pub leaf_index: u32,
+    pub amount: [u8; 32],
+    pub blinding: [u8; 32],
+    pub merkle_path: Vec<[u8; 32]>,
+    pub path_indices: Vec<bool>,
+}
+
+/// The nullifier ties the hidden amount to the deposit being spent and the
+/// root shows that deposit is in the pool; the amount itself only appears
+/// inside `amount_commitment`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfidentialPublicInputs {
+    pub nullifier: [u8; 32],
+    pub amount_commitment: [u8; 32],
+    pub merkle_root: [u8; 32],
+}
+
+impl ConfidentialWitness {
+    pub fn new(
+        nullifier_seed: [u8; 32],
+        leaf_index: u32,
+        amount: u64,
+        blinding: [u8; 32],
+        merkle_path: Vec<[u8; 32]>,
+        path_indices: Vec<bool>,
+    ) -> Self {
+        Self {
+            nullifier_seed,
+            leaf_index,
+            amount: u64_to_bytes(amount),
+            blinding,
+            merkle_path,
+            path_indices,
+        }
+    }
+
+    pub fn public_inputs(&self) -> ConfidentialPublicInputs {
+        let nullifier_seed = field_from_bytes::<Fr>(&self.nullifier_seed);
+        let amount = field_from_bytes::<Fr>(&self.amount);
+        let nullifier = compute_nullifier(nullifier_seed, Fr::from(self.leaf_index as u64));
+        let amount_commitment = poseidon_hash_native(&[amount, field_from_bytes::<Fr>(&self.blinding)]);
+
+        let zeros = zero_hashes::<Fr>(MERKLE_DEPTH);
+        let siblings: Vec<Fr> = (0..MERKLE_DEPTH)
+            .map(|level| self.merkle_path.get(level).map(field_from_bytes::<Fr>).unwrap_or(zeros[level]))
+            .collect();
+        let indices: Vec<bool> = (0..MERKLE_DEPTH)
+            .map(|level| self.path_indices.get(level).copied().unwrap_or(false))
+            .collect();
+        let merkle_root = fold_merkle_path(
+            poseidon_hash_native(&[nullifier_seed, amount]),
+            &siblings,
+            &indices,
+            |left, right| poseidon_hash_native(&[*left, *right]),
+        );
+
+        ConfidentialPublicInputs {
+            nullifier: field_to_bytes(nullifier),
+            amount_commitment: field_to_bytes(amount_commitment),
+            merkle_root: field_to_bytes(merkle_root),
+        }
+    }
+}
+
+impl ConfidentialPublicInputs {
+    /// Values of the instance column, in row order: nullifier, amount
+    /// commitment, merkle root.
+    pub fn instances(&self) -> Result<Vec<Fr>, CircuitError> {
+        let nullifier = field_from_canonical_bytes::<Fr>(&self.nullifier)
+            .ok_or_else(|| CircuitError::InvalidInput("Nullifier is not a canonical field element".into()))?;
+        let amount_commitment = field_from_canonical_bytes::<Fr>(&self.amount_commitment)
+            .ok_or_else(|| CircuitError::InvalidInput("Amount commitment is not a canonical field element".into()))?;
+        let merkle_root = field_from_canonical_bytes::<Fr>(&self.merkle_root)
+            .ok_or_else(|| CircuitError::InvalidInput("Merkle root is not a canonical field element".into()))?;
+        Ok(vec![nullifier, amount_commitment, merkle_root])
+    }
+}
+
+/// Pool leaf of a confidential deposit: `Poseidon(nullifier_seed, amount)`.
+/// Spending proves this leaf is in the tree, so the hidden amount is the one
+/// that was deposited under the seed behind the nullifier.
+pub fn confidential_leaf(nullifier_seed: &[u8; 32], amount: u64) -> [u8; 32] {
+    field_to_bytes(poseidon_hash_native(&[field_from_bytes::<Fr>(nullifier_seed), Fr::from(amount)]))
+}
+
+/// Hiding commitment to `amount`: `Poseidon(amount, blinding)`. The same
+/// amount under two blindings gives unrelated commitments.
+///
+/// This is a hash commitment rather than a Pedersen one. Pedersen would make
+/// commitments additively homomorphic, but opening it in-circuit needs scalar
+/// multiplication on a curve embedded over `Fr`, which costs far more rows
+/// than one Poseidon permutation. Nothing in the vault adds commitments
+/// together, so the homomorphism would go unused.
+pub fn amount_commitment(amount: u64, blinding: &[u8; 32]) -> [u8; 32] {
+    field_to_bytes(poseidon_hash_native(&[Fr::from(amount), field_from_bytes::<Fr>(blinding)]))
+}
+
+/// Fresh blinding factor, uniform over the field like deposit secrets.
+pub fn random_blinding() -> [u8; 32] {
+    field_to_bytes(Fr::random(OsRng))
+}
+
+/// Proves that `amount_commitment` opens to an amount below `2^AMOUNT_BITS`,
+/// that the pool at `merkle_root` holds a leaf binding that amount to the
+/// nullifier seed, and that `nullifier` is derived from that seed and leaf,
+/// without revealing the amount or the leaf.
+#[derive(Clone, Debug)]
+pub struct ConfidentialWithdrawalCircuit<F: PrimeField> {
+    pub witness: Option<ConfidentialWitness>,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> Default for ConfidentialWithdrawalCircuit<F> {
+    fn default() -> Self {
+        Self {
+            witness: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: PrimeField> ConfidentialWithdrawalCircuit<F> {
+    pub fn new(witness: ConfidentialWitness) -> Self {
+        Self {
+            witness: Some(witness),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Number of values expected in each instance column.
+    pub fn num_instances() -> Vec<usize> {
+        vec![3]
+    }
+}
+
+impl<F: PrimeField> Circuit<F> for ConfidentialWithdrawalCircuit<F> {
+    type Config = ConfidentialConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let constants = meta.fixed_column();
+        let instance = meta.instance_column();
+
+        meta.enable_constant(constants);
+        meta.enable_equality(instance);
+        for col in advice.iter() {
+            meta.enable_equality(*col);
+        }
+
+        let merkle_path = MerklePathChip::configure(meta, advice);
+        let round_constants = [meta.fixed_column(), meta.fixed_column(), meta.fixed_column()];
+        let poseidon = PoseidonChip::configure(meta, [advice[0], advice[1], advice[2]], round_constants);
+        let range_check = RangeCheckChip::configure(meta, advice[3], advice[4]);
+
+        ConfidentialConfig {
+            advice,
+            instance,
+            merkle_path,
+            poseidon,
+            range_check,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let witness = self.witness.as_ref();
+
+        let (nullifier_seed, leaf_index, amount, blinding) = layouter.assign_region(
+            || "confidential_inputs",
+            |mut region| {
+                let nullifier_seed = region.assign_advice(
+                    || "nullifier_seed",
+                    config.advice[0],
+                    0,
+                    || witness.map(|w| Value::known(field_from_bytes::<F>(&w.nullifier_seed))).unwrap_or(Value::unknown()),
+                )?;
+                let leaf_index = region.assign_advice(
+                    || "leaf_index",
+                    config.advice[1],
+                    0,
+                    || witness.map(|w| Value::known(F::from(w.leaf_index as u64))).unwrap_or(Value::unknown()),
+                )?;
+                let amount = region.assign_advice(
+                    || "amount",
+                    config.advice[0],
+                    1,
+                    || witness.map(|w| Value::known(field_from_bytes::<F>(&w.amount))).unwrap_or(Value::unknown()),
+                )?;
+                let blinding = region.assign_advice(
+                    || "blinding",
+                    config.advice[1],
+                    1,
+                    || witness.map(|w| Value::known(field_from_bytes::<F>(&w.blinding))).unwrap_or(Value::unknown()),
+                )?;
+
+                Ok((nullifier_seed, leaf_index, amount, blinding))
+            },
+        )?;
+
+        let indices: Vec<Value<bool>> = (0..MERKLE_DEPTH)
+            .map(|level| {
+                witness
+                    .map(|w| Value::known(w.path_indices.get(level).copied().unwrap_or(false)))
+                    .unwrap_or(Value::unknown())
+            })
+            .collect();
+        let zeros = zero_hashes::<F>(MERKLE_DEPTH);
+        let siblings: Vec<Value<F>> = (0..MERKLE_DEPTH)
+            .map(|level| {
+                witness
+                    .map(|w| Value::known(w.merkle_path.get(level).map(field_from_bytes::<F>).unwrap_or(zeros[level])))
+                    .unwrap_or(Value::unknown())
+            })
+            .collect();
+
+        let merkle_path = MerklePathChip::<F>::construct(config.merkle_path.clone());
+        let path_bits = merkle_path.path_bits(layouter.namespace(|| "path_index_bits"), &leaf_index, &indices)?;
+
+        let poseidon = PoseidonChip::<F>::construct(config.poseidon.clone());
+        let leaf = poseidon.hash(layouter.namespace(|| "leaf"), &[nullifier_seed.clone(), amount.clone()])?;
+        let nullifier = poseidon.hash(layouter.namespace(|| "nullifier"), &[nullifier_seed, leaf_index])?;
+        let commitment = poseidon.hash(layouter.namespace(|| "amount_commitment"), &[amount.clone(), blinding])?;
+        let root = merkle_path.root(layouter.namespace(|| "merkle_path"), &poseidon, leaf, &path_bits, &siblings)?;
+
+        RangeCheckChip::construct(config.range_check.clone())
+            .range_check(layouter.namespace(|| "amount_range"), &amount, AMOUNT_BITS)?;
+
+        layouter.constrain_instance(nullifier.cell(), config.instance, NULLIFIER_ROW)?;
+        layouter.constrain_instance(commitment.cell(), config.instance, AMOUNT_COMMITMENT_ROW)?;
+        layouter.constrain_instance(root.cell(), config.instance, MERKLE_ROOT_ROW)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::dev::MockProver;
+    use crate::merkle::PoseidonMerkleTree;
+
+    const CONFIDENTIAL_TEST_K: u32 = 11;
+
+    fn mock_prover(witness: ConfidentialWitness) -> MockProver<Fr> {
+        let instances = witness.public_inputs().instances().unwrap();
+        let circuit = ConfidentialWithdrawalCircuit::<Fr>::new(witness);
+        MockProver::run(CONFIDENTIAL_TEST_K, &circuit, vec![instances]).unwrap()
+    }
+
+    /// A pool holding one confidential deposit of `amount` under `seed` at
+    /// leaf 3, and the witness spending it.
+    fn pool_witness(seed: [u8; 32], amount: u64, blinding: [u8; 32]) -> (PoseidonMerkleTree, ConfidentialWitness) {
+        let mut tree = PoseidonMerkleTree::empty(MERKLE_DEPTH);
+        tree.insert_batch(0, &[[1u8; 32], [2u8; 32], [3u8; 32]]).unwrap();
+        tree.insert(3, confidential_leaf(&seed, amount)).unwrap();
+
+        let (merkle_path, path_indices) = tree.generate_proof_for_leaf(&confidential_leaf(&seed, amount), 3).unwrap();
+        (tree, ConfidentialWitness::new(seed, 3, amount, blinding, merkle_path, path_indices))
+    }
+
+    #[test]
+    fn test_confidential_withdrawal() {
+        let blinding = random_blinding();
+        let (tree, witness) = pool_witness([4u8; 32], 1_000, blinding);
+        let public_inputs = witness.public_inputs();
+
+        assert_eq!(public_inputs.merkle_root, tree.root());
+        assert_eq!(public_inputs.amount_commitment, amount_commitment(1_000, &blinding));
+        assert_eq!(
+            public_inputs.nullifier,
+            field_to_bytes(compute_nullifier(field_from_bytes::<Fr>(&[4u8; 32]), Fr::from(3u64)))
+        );
+        assert_ne!(amount_commitment(1_000, &random_blinding()), public_inputs.amount_commitment);
+
+        mock_prover(witness.clone()).verify().unwrap();
+
+        let mut wrong_amount = public_inputs.instances().unwrap();
+        wrong_amount[AMOUNT_COMMITMENT_ROW] = poseidon_hash_native(&[Fr::from(1_001u64), field_from_bytes::<Fr>(&blinding)]);
+        let circuit = ConfidentialWithdrawalCircuit::<Fr>::new(witness);
+        assert!(MockProver::run(CONFIDENTIAL_TEST_K, &circuit, vec![wrong_amount]).unwrap().verify().is_err());
+    }
+
+    #[test]
+    fn test_rejects_unrelated_seed() {
+        let (tree, deposited) = pool_witness([4u8; 32], 1_000, random_blinding());
+
+        // Same path, same amount, but a seed that was never deposited: its
+        // nullifier would be fresh, so the pool root is all that stops it.
+        let mut unrelated = deposited.clone();
+        unrelated.nullifier_seed = [5u8; 32];
+        let mut instances = unrelated.public_inputs().instances().unwrap();
+        assert_ne!(field_to_bytes(instances[MERKLE_ROOT_ROW]), tree.root());
+        instances[MERKLE_ROOT_ROW] = field_from_canonical_bytes::<Fr>(&tree.root()).unwrap();
+
+        let circuit = ConfidentialWithdrawalCircuit::<Fr>::new(unrelated);
+        assert!(MockProver::run(CONFIDENTIAL_TEST_K, &circuit, vec![instances]).unwrap().verify().is_err());
+
+        // Spending the real deposit with a larger hidden amount fails the same way.
+        let mut inflated = deposited;
+        inflated.amount = u64_to_bytes(1_001);
+        let mut instances = inflated.public_inputs().instances().unwrap();
+        instances[MERKLE_ROOT_ROW] = field_from_canonical_bytes::<Fr>(&tree.root()).unwrap();
+
+        let circuit = ConfidentialWithdrawalCircuit::<Fr>::new(inflated);
+        assert!(MockProver::run(CONFIDENTIAL_TEST_K, &circuit, vec![instances]).unwrap().verify().is_err());
+    }
+
+    #[test]
+    fn test_rejects_over_range_amount() {
+        let (_, mut witness) = pool_witness([4u8; 32], u64::MAX, random_blinding());
+        mock_prover(witness.clone()).verify().unwrap();
+
+        // 2^64: one past the largest amount. The commitment still opens and
+        // the root is recomputed for it, so only the range check can reject it.
+        witness.amount = [0u8; 32];
+        witness.amount[AMOUNT_BITS / 8] = 1;
+        assert!(mock_prover(witness).verify().is_err());
+    }
+}
@@ -0,0 +1,184 @@
+//! Byte <-> field conversions.
+//!
+//! The canonical encoding is little-endian: byte 0 is the least significant.
+//! This is the order `PrimeField::to_repr` uses for BN256 and the order of
+//! `u32::to_le_bytes`/`u64::to_le_bytes`, so indices and amounts serialized
+//! for hashing line up with their field representation.
+
+use ff::PrimeField;
+use halo2_proofs::{circuit::Value, halo2curves::bn256::Fr};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Longest byte string that always fits: any 31-byte value is below the BN256
+/// scalar modulus, so it decodes without a reduction.
+pub const FIELD_INPUT_BYTES: usize = 31;
+
+pub fn field_to_bytes<F: PrimeField>(value: F) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    let repr = value.to_repr();
+    let len = repr.as_ref().len().min(32);
+    bytes[..len].copy_from_slice(&repr.as_ref()[..len]);
+    bytes
+}
+
+/// Decodes all 32 bytes as a little-endian integer reduced modulo the field.
+/// Every byte is significant, so inputs differing only in byte 31 never
+/// collide unless they are congruent modulo the field.
+pub fn field_from_bytes<F: PrimeField>(bytes: &[u8; 32]) -> F {
+    let base = F::from(256u64);
+    bytes
+        .iter()
+        .rev()
+        .fold(F::ZERO, |acc, byte| acc * base + F::from(*byte as u64))
+}
+
+/// Strict decoding of a full 32-byte representation; `None` if the bytes are
+/// not the canonical encoding of a field element.
+pub fn field_from_canonical_bytes<F: PrimeField>(bytes: &[u8; 32]) -> Option<F> {
+    let mut repr = F::Repr::default();
+    if repr.as_ref().len() != bytes.len() {
+        return None;
+    }
+    repr.as_mut().copy_from_slice(bytes);
+    F::from_repr(repr).into()
+}
+
+/// `Fr` with serde support. It is written as its 32-byte little-endian repr
+/// and reading rejects any encoding at or above the modulus, so every value
+/// has exactly one serialized form.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SerField(pub Fr);
+
+impl From<Fr> for SerField {
+    fn from(value: Fr) -> Self {
+        SerField(value)
+    }
+}
+
+impl From<SerField> for Fr {
+    fn from(value: SerField) -> Self {
+        value.0
+    }
+}
+
+impl Serialize for SerField {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        field_to_bytes(self.0).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SerField {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = <[u8; 32]>::deserialize(deserializer)?;
+        field_from_canonical_bytes::<Fr>(&bytes)
+            .map(SerField)
+            .ok_or_else(|| D::Error::custom("field element is not canonically encoded"))
+    }
+}
+
+pub fn u64_to_bytes(value: u64) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes[..8].copy_from_slice(&value.to_le_bytes());
+    bytes
+}
+
+pub(crate) fn bytes_to_field<F: PrimeField>(bytes: &[u8; 32]) -> Value<F> {
+    Value::known(field_from_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::halo2curves::bn256::Fr;
+
+    #[test]
+    fn test_u64_encoding_matches_field() {
+        let value = 0x0123_4567_89ab_cdefu64;
+        let bytes = u64_to_bytes(value);
+
+        assert_eq!(field_from_bytes::<Fr>(&bytes), Fr::from(value));
+        assert_eq!(field_to_bytes(Fr::from(value)), bytes);
+        assert_eq!(field_from_canonical_bytes::<Fr>(&bytes), Some(Fr::from(value)));
+    }
+
+    #[test]
+    fn test_roundtrip_through_repr() {
+        let mut bytes = [0u8; 32];
+        for (i, b) in bytes.iter_mut().take(FIELD_INPUT_BYTES).enumerate() {
+            *b = (i as u8).wrapping_mul(37).wrapping_add(11);
+        }
+
+        let lenient = field_from_bytes::<Fr>(&bytes);
+        let strict = field_from_canonical_bytes::<Fr>(&bytes).unwrap();
+        assert_eq!(lenient, strict);
+        assert_eq!(field_to_bytes(lenient), bytes);
+        assert_eq!(Fr::from_bytes(&bytes).unwrap(), lenient);
+    }
+
+    #[test]
+    fn test_decoding_keeps_top_byte() {
+        let node = -Fr::from(1u64);
+        let bytes = field_to_bytes(node);
+        assert_ne!(bytes[31], 0);
+        assert_eq!(field_from_bytes::<Fr>(&bytes), node);
+
+        let mut low = bytes;
+        low[31] = 0;
+        assert_ne!(field_from_bytes::<Fr>(&low), node);
+
+        // Non-canonical encodings reduce: p decodes to zero, p + 1 to one.
+        let mut modulus = bytes;
+        modulus[0] += 1;
+        assert_eq!(field_from_bytes::<Fr>(&modulus), Fr::from(0u64));
+        modulus[0] += 1;
+        assert_eq!(field_from_bytes::<Fr>(&modulus), Fr::from(1u64));
+    }
+
+    #[test]
+    fn test_bytes_to_field_matches_commitment_leaf() {
+        use crate::commitment::commitment_bytes;
+        use crate::withdrawal_circuit::compute_commitment;
+
+        let secret = [0x2au8; 32];
+        let seed = [0x17u8; 32];
+        let leaf = compute_commitment(field_from_bytes::<Fr>(&secret), field_from_bytes::<Fr>(&seed));
+        assert_eq!(commitment_bytes(&secret, &seed), field_to_bytes(leaf));
+
+        bytes_to_field::<Fr>(&secret)
+            .zip(bytes_to_field::<Fr>(&seed))
+            .assert_if_known(|(s, n)| compute_commitment(*s, *n) == leaf);
+
+        let mut changed = secret;
+        changed[31] ^= 1;
+        bytes_to_field::<Fr>(&changed).assert_if_known(|s| *s != field_from_bytes::<Fr>(&secret));
+        assert_ne!(commitment_bytes(&changed, &seed), commitment_bytes(&secret, &seed));
+    }
+
+    #[test]
+    fn test_ser_field_roundtrip() {
+        for value in [Fr::from(0u64), Fr::from(42u64), -Fr::from(1u64)] {
+            let json = serde_json::to_string(&SerField(value)).unwrap();
+            assert_eq!(serde_json::from_str::<SerField>(&json).unwrap(), SerField(value));
+            assert_eq!(json, serde_json::to_string(&field_to_bytes(value)).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_ser_field_rejects_non_canonical() {
+        // p - 1 ends in 0x00, so bumping byte 0 gives p itself.
+        let mut modulus = field_to_bytes(-Fr::from(1u64));
+        modulus[0] += 1;
+        let mut modulus_plus_5 = modulus;
+        modulus_plus_5[0] += 5;
+
+        for bytes in [modulus, modulus_plus_5, [0xff; 32]] {
+            let json = serde_json::to_string(&bytes).unwrap();
+            assert!(serde_json::from_str::<SerField>(&json).is_err());
+        }
+    }
+
+    #[test]
+    fn test_canonical_rejects_out_of_range() {
+        assert!(field_from_canonical_bytes::<Fr>(&[0xff; 32]).is_none());
+    }
+}
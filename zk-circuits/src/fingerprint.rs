@@ -0,0 +1,120 @@
+use ff::Field;
+use halo2_proofs::plonk::{Circuit, ConstraintSystem};
+use sha2::{Sha256, Digest};
+
+const FINGERPRINT_DOMAIN: &[u8] = b"zkenclave-circuit-fingerprint-v2";
+
+/// Hashes the layout-relevant shape of a constraint system: column, selector
+/// and gate counts, the degree, and every gate polynomial. Keys generated for
+/// one shape are unusable with another, so `keygen` writes this at the head of
+/// every key file and refuses to read a key whose header differs.
+pub fn constraint_system_fingerprint<F: Field>(cs: &ConstraintSystem<F>) -> [u8; 32] {
+    let counts = [
+        cs.num_advice_columns(),
+        cs.num_fixed_columns(),
+        cs.num_instance_columns(),
+        cs.num_selectors(),
+        cs.gates().len(),
+        cs.degree(),
+    ];
+
+    let mut hasher = Sha256::new();
+    hasher.update(FINGERPRINT_DOMAIN);
+    for count in counts {
+        hasher.update((count as u64).to_le_bytes());
+    }
+    // A gate whose polynomial changes keeps every count above, so the
+    // expressions themselves go into the hash.
+    for polynomial in cs.gates().iter().flat_map(|gate| gate.polynomials()) {
+        let repr = format!("{:?}", polynomial);
+        hasher.update((repr.len() as u64).to_le_bytes());
+        hasher.update(repr.as_bytes());
+    }
+    hasher.finalize().into()
+}
+
+pub fn circuit_fingerprint<F: Field, C: Circuit<F>>() -> [u8; 32] {
+    let mut cs = ConstraintSystem::<F>::default();
+    let _ = C::configure(&mut cs);
+    constraint_system_fingerprint(&cs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::{
+        circuit::Layouter,
+        halo2curves::bn256::Fr,
+        plonk::Error,
+    };
+    use crate::association_circuit::AssociationCircuit;
+    use crate::keygen::check_key_fingerprint;
+    use crate::withdrawal_circuit::{WithdrawalCircuit, WithdrawalConfig};
+
+    #[derive(Default)]
+    struct WideWithdrawalCircuit(WithdrawalCircuit<Fr>);
+
+    impl Circuit<Fr> for WideWithdrawalCircuit {
+        type Config = WithdrawalConfig;
+        type FloorPlanner = <WithdrawalCircuit<Fr> as Circuit<Fr>>::FloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let config = WithdrawalCircuit::<Fr>::configure(meta);
+            let _extra = meta.advice_column();
+            config
+        }
+
+        fn synthesize(&self, config: Self::Config, layouter: impl Layouter<Fr>) -> Result<(), Error> {
+            self.0.synthesize(config, layouter)
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_is_deterministic() {
+        let a = circuit_fingerprint::<Fr, WithdrawalCircuit<Fr>>();
+        let b = circuit_fingerprint::<Fr, WithdrawalCircuit<Fr>>();
+        assert_eq!(a, b);
+    }
+
+    /// Fails whenever a layout change lands without rerunning `setup`.
+    #[test]
+    fn test_fingerprint_matches_embedded() {
+        check_key_fingerprint::<WithdrawalCircuit<Fr>>(include_bytes!("withdrawal_vk.bin")).unwrap();
+        check_key_fingerprint::<WithdrawalCircuit<Fr>>(include_bytes!("withdrawal_pk.bin")).unwrap();
+        check_key_fingerprint::<AssociationCircuit<Fr>>(include_bytes!("association_vk.bin")).unwrap();
+        check_key_fingerprint::<AssociationCircuit<Fr>>(include_bytes!("association_pk.bin")).unwrap();
+    }
+
+    /// A matching header isn't enough: the embedded vks must be the ones
+    /// `keygen_vk` derives today from the embedded params.
+    #[test]
+    fn test_embedded_keys_are_current() {
+        use halo2_proofs::{
+            halo2curves::bn256::Bn256,
+            plonk::keygen_vk,
+            poly::{commitment::Params, kzg::commitment::ParamsKZG},
+        };
+        use crate::keygen::read_circuit_vk;
+
+        let params = ParamsKZG::<Bn256>::read(&mut &include_bytes!("params.bin")[..]).unwrap();
+
+        let embedded = read_circuit_vk::<WithdrawalCircuit<Fr>, _>(&include_bytes!("withdrawal_vk.bin")[..]).unwrap();
+        let fresh = keygen_vk(&params, &WithdrawalCircuit::<Fr>::default()).unwrap();
+        assert_eq!(embedded.transcript_repr(), fresh.transcript_repr());
+
+        let embedded = read_circuit_vk::<AssociationCircuit<Fr>, _>(&include_bytes!("association_vk.bin")[..]).unwrap();
+        let fresh = keygen_vk(&params, &AssociationCircuit::<Fr>::default()).unwrap();
+        assert_eq!(embedded.transcript_repr(), fresh.transcript_repr());
+    }
+
+    #[test]
+    fn test_fingerprint_changes_with_extra_column() {
+        let original = circuit_fingerprint::<Fr, WithdrawalCircuit<Fr>>();
+        let widened = circuit_fingerprint::<Fr, WideWithdrawalCircuit>();
+        assert_ne!(original, widened);
+    }
+}
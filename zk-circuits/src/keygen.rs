@@ -0,0 +1,218 @@
+use std::io::{BufReader, BufWriter, Read, Write};
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::{keygen_pk, keygen_vk, Circuit, ProvingKey, VerifyingKey},
+    poly::{commitment::Params, kzg::commitment::ParamsKZG},
+    SerdeFormat,
+};
+
+use crate::fingerprint::circuit_fingerprint;
+use crate::CircuitError;
+
+/// Reads a previously written SRS and trims it to `k`, so one large SRS can
+/// be reused for every circuit size instead of running `ParamsKZG::setup`
+/// again. The whole SRS is read into memory before it is downsized.
+pub fn read_srs<R: Read>(reader: R, k: u32) -> Result<ParamsKZG<Bn256>, CircuitError> {
+    let mut params = ParamsKZG::<Bn256>::read(&mut BufReader::new(reader))
+        .map_err(|e| CircuitError::Serialization(format!("Cannot read SRS: {}", e)))?;
+    if params.k() < k {
+        return Err(CircuitError::InvalidInput(format!(
+            "SRS supports K={} but K={} was requested",
+            params.k(),
+            k
+        )));
+    }
+    if params.k() > k {
+        params.downsize(k);
+    }
+    Ok(params)
+}
+
+pub fn write_params<W: Write>(params: &ParamsKZG<Bn256>, writer: W) -> Result<(), CircuitError> {
+    let mut writer = BufWriter::new(writer);
+    params.write(&mut writer)
+        .and_then(|_| writer.flush())
+        .map_err(|e| CircuitError::Serialization(format!("Cannot write params: {}", e)))
+}
+
+/// Generates keys for `circuit` and writes them out. Each key is streamed to
+/// its writer one component at a time, so serialization adds a buffer rather
+/// than a second copy of the key; `keygen_pk` itself still holds the whole key.
+///
+/// With `max_memory_mib` set, `check_memory_cap` runs after each key is
+/// generated and before it is written, so a run that goes over the cap stops
+/// before serializing the key.
+///
+/// Both files start with `circuit_fingerprint::<Fr, C>()`, so the layout a key
+/// was made for travels with it; `read_circuit_vk`/`read_circuit_pk` check it.
+pub fn write_circuit_keys<C: Circuit<Fr>, V: Write, P: Write>(
+    params: &ParamsKZG<Bn256>,
+    circuit: &C,
+    vk_writer: V,
+    pk_writer: P,
+    max_memory_mib: Option<u64>,
+) -> Result<VerifyingKey<G1Affine>, CircuitError> {
+    let vk = keygen_vk(params, circuit)
+        .map_err(|e| CircuitError::ProofGeneration(format!("VK generation failed: {:?}", e)))?;
+    check_memory_cap(max_memory_mib, "vk generation")?;
+    write_vk::<C, _>(&vk, vk_writer)?;
+
+    let pk = keygen_pk(params, vk.clone(), circuit)
+        .map_err(|e| CircuitError::ProofGeneration(format!("PK generation failed: {:?}", e)))?;
+    check_memory_cap(max_memory_mib, "pk generation")?;
+
+    let mut pk_writer = BufWriter::new(pk_writer);
+    pk_writer.write_all(&circuit_fingerprint::<Fr, C>())
+        .and_then(|_| pk.write(&mut pk_writer, SerdeFormat::RawBytes))
+        .and_then(|_| pk_writer.flush())
+        .map_err(|e| CircuitError::Serialization(format!("Cannot write pk: {}", e)))?;
+
+    Ok(vk)
+}
+
+/// Writes `vk` behind `C`'s fingerprint, in the format `read_circuit_vk` reads.
+pub fn write_vk<C: Circuit<Fr>, W: Write>(vk: &VerifyingKey<G1Affine>, writer: W) -> Result<(), CircuitError> {
+    let mut writer = BufWriter::new(writer);
+    writer.write_all(&circuit_fingerprint::<Fr, C>())
+        .and_then(|_| vk.write(&mut writer, SerdeFormat::RawBytes))
+        .and_then(|_| writer.flush())
+        .map_err(|e| CircuitError::Serialization(format!("Cannot write vk: {}", e)))
+}
+
+/// Checks the fingerprint header of a key file against the current layout of
+/// `C`, without decoding the key itself.
+pub fn check_key_fingerprint<C: Circuit<Fr>>(bytes: &[u8]) -> Result<(), CircuitError> {
+    read_fingerprint::<C, _>(&mut &bytes[..])
+}
+
+fn read_fingerprint<C: Circuit<Fr>, R: Read>(reader: &mut R) -> Result<(), CircuitError> {
+    let mut header = [0u8; 32];
+    reader.read_exact(&mut header)
+        .map_err(|e| CircuitError::Serialization(format!("Cannot read key fingerprint: {}", e)))?;
+    if header != circuit_fingerprint::<Fr, C>() {
+        return Err(CircuitError::InvalidInput(
+            "Key was generated for a different circuit layout; rerun setup".into(),
+        ));
+    }
+    Ok(())
+}
+
+pub fn read_circuit_vk<C: Circuit<Fr>, R: Read>(reader: R) -> Result<VerifyingKey<G1Affine>, CircuitError> {
+    let mut reader = BufReader::new(reader);
+    read_fingerprint::<C, _>(&mut reader)?;
+    VerifyingKey::<G1Affine>::read::<_, C>(&mut reader, SerdeFormat::RawBytes)
+        .map_err(|e| CircuitError::Serialization(format!("Cannot read vk: {}", e)))
+}
+
+pub fn read_circuit_pk<C: Circuit<Fr>, R: Read>(reader: R) -> Result<ProvingKey<G1Affine>, CircuitError> {
+    let mut reader = BufReader::new(reader);
+    read_fingerprint::<C, _>(&mut reader)?;
+    ProvingKey::<G1Affine>::read::<_, C>(&mut reader, SerdeFormat::RawBytes)
+        .map_err(|e| CircuitError::Serialization(format!("Cannot read pk: {}", e)))
+}
+
+/// Peak resident set size of this process in KiB, where the platform reports
+/// it (`VmHWM` on Linux).
+pub fn peak_memory_kib() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+}
+
+/// Fails if peak memory so far is above `max_mib`, naming `stage`. A cap
+/// cannot be checked where `peak_memory_kib` has nothing to report, so that
+/// fails too instead of passing unchecked. `None` means no cap.
+pub fn check_memory_cap(max_mib: Option<u64>, stage: &str) -> Result<(), CircuitError> {
+    let Some(max_mib) = max_mib else {
+        return Ok(());
+    };
+    let peak_kib = peak_memory_kib().ok_or_else(|| {
+        CircuitError::InvalidInput("Peak memory is not reported on this platform; cannot enforce a memory cap".into())
+    })?;
+    if peak_kib > max_mib * 1024 {
+        return Err(CircuitError::InvalidInput(format!(
+            "Peak memory {} MiB after {} exceeds the {} MiB cap",
+            peak_kib / 1024,
+            stage,
+            max_mib
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+    use crate::association_circuit::AssociationCircuit;
+    use crate::withdrawal_circuit::{WithdrawalCircuit, WithdrawalWitness, MERKLE_DEPTH};
+    use crate::{prove_withdrawal, setup_withdrawal_circuit, verify_withdrawal, ProverParams, VerifierParams};
+
+    #[test]
+    fn test_written_keys_match_in_memory_setup() {
+        let mut srs = Vec::new();
+        write_params(&ParamsKZG::<Bn256>::setup(12, OsRng), &mut srs).unwrap();
+        let params = read_srs(&srs[..], 11).unwrap();
+        assert_eq!(params.k(), 11);
+        assert!(read_srs(&srs[..], 13).is_err());
+
+        let (mut vk_bytes, mut pk_bytes) = (Vec::new(), Vec::new());
+        let vk = write_circuit_keys(&params, &WithdrawalCircuit::<Fr>::default(), &mut vk_bytes, &mut pk_bytes, None).unwrap();
+        let read_vk = read_circuit_vk::<WithdrawalCircuit<Fr>, _>(&vk_bytes[..]).unwrap();
+        let read_pk = read_circuit_pk::<WithdrawalCircuit<Fr>, _>(&pk_bytes[..]).unwrap();
+        assert_eq!(read_vk.transcript_repr(), vk.transcript_repr());
+
+        // The header names the circuit, so another circuit's reader refuses it.
+        assert!(read_circuit_vk::<AssociationCircuit<Fr>, _>(&vk_bytes[..]).is_err());
+        assert!(check_key_fingerprint::<AssociationCircuit<Fr>>(&pk_bytes).is_err());
+        check_key_fingerprint::<WithdrawalCircuit<Fr>>(&pk_bytes).unwrap();
+
+        let (in_memory_prover, in_memory_verifier) = setup_withdrawal_circuit(11).unwrap();
+        assert_eq!(read_vk.get_domain().k(), in_memory_verifier.vk.get_domain().k());
+
+        let written_prover = ProverParams { params: params.clone(), pk: read_pk };
+        let written_verifier = VerifierParams { params, vk: read_vk };
+
+        let witness = WithdrawalWitness {
+            secret: [9u8; 32],
+            merkle_path: vec![[0u8; 32]; MERKLE_DEPTH],
+            path_indices: vec![false; MERKLE_DEPTH],
+            ..Default::default()
+        };
+        let public_inputs = witness.public_inputs::<Fr>([0xab; 20]);
+        let instances = public_inputs.instances::<Fr>().unwrap();
+
+        for (prover, verifier) in [(&written_prover, &written_verifier), (&in_memory_prover, &in_memory_verifier)] {
+            let circuit = WithdrawalCircuit::new(witness.clone(), public_inputs.clone());
+            let proof = prove_withdrawal(prover, circuit, &[&instances]).unwrap();
+            assert!(verify_withdrawal(verifier, &proof, &[&instances]).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_memory_cap_stops_before_writing_keys() {
+        let params = ParamsKZG::<Bn256>::setup(11, OsRng);
+        let (mut vk_bytes, mut pk_bytes) = (Vec::new(), Vec::new());
+
+        let err = write_circuit_keys(&params, &WithdrawalCircuit::<Fr>::default(), &mut vk_bytes, &mut pk_bytes, Some(1))
+            .unwrap_err();
+        assert!(matches!(err, CircuitError::InvalidInput(_)));
+        assert!(vk_bytes.is_empty());
+        assert!(pk_bytes.is_empty());
+
+        check_memory_cap(None, "setup").unwrap();
+        if cfg!(target_os = "linux") {
+            assert!(err.to_string().contains("vk generation"));
+            check_memory_cap(Some(u64::MAX / 1024), "setup").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_peak_memory_is_reported_on_linux() {
+        if cfg!(target_os = "linux") {
+            assert!(peak_memory_kib().unwrap() > 0);
+        }
+    }
+}
@@ -1,7 +1,19 @@
 pub mod poseidon;
+pub mod backend;
 pub mod merkle;
+pub mod merkle_path;
 pub mod withdrawal_circuit;
 pub mod association_circuit;
+pub mod commitment;
+pub mod confidential_circuit;
+pub mod encoding;
+pub mod fingerprint;
+pub mod keygen;
+pub mod link_circuit;
+pub mod range_check;
+
+#[cfg(test)]
+mod linkability;
 
 #[cfg(target_arch = "wasm32")]
 mod wasm;
@@ -9,21 +21,22 @@ mod wasm;
 #[cfg(target_arch = "wasm32")]
 pub use wasm::*;
 
-pub use poseidon::{PoseidonChip, PoseidonConfig, poseidon_hash_native};
-pub use merkle::{MerkleTree, MerkleProof, merkle_hash};
+pub use poseidon::{PoseidonChip, PoseidonConfig, poseidon_hash_native, poseidon_sponge_native};
+pub use merkle::{MerkleHasher, MerkleTree, MerkleProof, PoseidonHasher, PoseidonMerkleTree, Sha256Hasher, fold_merkle_path, leaf_hash, merkle_hash, path_indices_to_index, verify_merkle_proof, verify_merkle_proof_with};
 pub use withdrawal_circuit::{WithdrawalCircuit, WithdrawalConfig};
+pub use link_circuit::{LinkCircuit, LinkConfig, LinkPublicInputs, LinkWitness};
+pub use commitment::{Deposit, generate_deposit};
+pub use confidential_circuit::{ConfidentialPublicInputs, ConfidentialWithdrawalCircuit, ConfidentialWitness};
+pub use backend::{CachedVerifier, Halo2Backend, ProvingBackend};
+pub use fingerprint::{circuit_fingerprint, constraint_system_fingerprint};
 
 use halo2_proofs::{
     halo2curves::bn256::{Bn256, Fr, G1Affine},
-    plonk::{keygen_pk, keygen_vk, create_proof, verify_proof, ProvingKey, VerifyingKey},
-    poly::kzg::{
-        commitment::{KZGCommitmentScheme, ParamsKZG},
-        multiopen::{ProverSHPLONK, VerifierSHPLONK},
-        strategy::SingleStrategy,
-    },
-    transcript::{Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer},
+    plonk::{keygen_pk, keygen_vk, Circuit, ProvingKey, VerifyingKey},
+    poly::kzg::commitment::ParamsKZG,
 };
 use rand::rngs::OsRng;
+use std::sync::atomic::{AtomicBool, Ordering};
 use thiserror::Error;
 use serde::{Serialize, Deserialize};
 
@@ -37,6 +50,14 @@ pub enum CircuitError {
     InvalidInput(String),
     #[error("Serialization error: {0}")]
     Serialization(String),
+    #[error("Witness does not reproduce the public merkle root")]
+    RootMismatch,
+    #[error("Witness does not reproduce the public nullifier")]
+    NullifierMismatch,
+    #[error("Witness amount differs from the public amount")]
+    AmountMismatch,
+    #[error("Witness leaf index differs from its merkle path bits")]
+    LeafIndexMismatch,
 }
 
 pub struct ProverParams {
@@ -49,10 +70,22 @@ pub struct VerifierParams {
     pub vk: VerifyingKey<G1Affine>,
 }
 
+impl ProverParams {
+    pub fn backend(&self) -> Halo2Backend<'_> {
+        Halo2Backend::prover(&self.params, &self.pk)
+    }
+}
+
+impl VerifierParams {
+    pub fn backend(&self) -> Halo2Backend<'_> {
+        Halo2Backend::verifier(&self.params, &self.vk)
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Proof {
     pub bytes: Vec<u8>,
-    pub public_inputs: Vec<[u8; 32]>,
+    pub public_inputs: Vec<encoding::SerField>,
 }
 
 impl Proof {
@@ -67,12 +100,19 @@ impl Proof {
 }
 
 pub fn setup_withdrawal_circuit(k: u32) -> Result<(ProverParams, VerifierParams), CircuitError> {
+    setup_circuit(k, &WithdrawalCircuit::<Fr>::default())
+}
+
+pub fn setup_link_circuit(k: u32) -> Result<(ProverParams, VerifierParams), CircuitError> {
+    setup_circuit(k, &LinkCircuit::<Fr>::default())
+}
+
+fn setup_circuit<C: Circuit<Fr>>(k: u32, circuit: &C) -> Result<(ProverParams, VerifierParams), CircuitError> {
     let params = ParamsKZG::<Bn256>::setup(k, OsRng);
-    let circuit = WithdrawalCircuit::<Fr>::default();
     
-    let vk = keygen_vk(&params, &circuit)
+    let vk = keygen_vk(&params, circuit)
         .map_err(|e| CircuitError::ProofGeneration(format!("VK generation failed: {:?}", e)))?;
-    let pk = keygen_pk(&params, vk.clone(), &circuit)
+    let pk = keygen_pk(&params, vk.clone(), circuit)
         .map_err(|e| CircuitError::ProofGeneration(format!("PK generation failed: {:?}", e)))?;
     
     Ok((
@@ -86,50 +126,188 @@ pub fn prove_withdrawal(
     circuit: WithdrawalCircuit<Fr>,
     public_inputs: &[&[Fr]],
 ) -> Result<Proof, CircuitError> {
-    let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
-    
-    create_proof::<KZGCommitmentScheme<Bn256>, ProverSHPLONK<'_, Bn256>, _, _, _, _>(
-        &prover.params,
-        &prover.pk,
-        &[circuit],
-        &[public_inputs],
-        OsRng,
-        &mut transcript,
-    )
-    .map_err(|e| CircuitError::ProofGeneration(format!("Proof creation failed: {:?}", e)))?;
-    
-    let proof_bytes = transcript.finalize();
-    
-    let pi_bytes: Vec<[u8; 32]> = public_inputs.iter()
-        .flat_map(|arr| arr.iter())
-        .map(|fr| fr.to_bytes())
-        .collect();
+    prove_withdrawal_with(&prover.backend(), circuit, public_inputs)
+}
+
+pub fn prove_withdrawal_with<B: ProvingBackend<WithdrawalCircuit<Fr>>>(
+    backend: &B,
+    circuit: WithdrawalCircuit<Fr>,
+    public_inputs: &[&[Fr]],
+) -> Result<Proof, CircuitError> {
+    if let (Some(witness), Some(expected)) = (&circuit.witness, &circuit.public_inputs) {
+        witness.validate::<Fr>(expected)?;
+    }
+    prove_with(backend, circuit, public_inputs)
+}
+
+pub fn prove_link(
+    prover: &ProverParams,
+    witness: LinkWitness,
+    public_inputs: &LinkPublicInputs,
+) -> Result<Proof, CircuitError> {
+    let instances = public_inputs.instances()?;
+    prove_circuit(prover, LinkCircuit::<Fr>::new(witness), &[&instances])
+}
+
+fn prove_circuit<C: Circuit<Fr>>(
+    prover: &ProverParams,
+    circuit: C,
+    public_inputs: &[&[Fr]],
+) -> Result<Proof, CircuitError> {
+    prove_with(&prover.backend(), circuit, public_inputs)
+}
+
+fn prove_with<C, B: ProvingBackend<C>>(
+    backend: &B,
+    circuit: C,
+    public_inputs: &[&[Fr]],
+) -> Result<Proof, CircuitError> {
+    let proof_bytes = backend.prove(circuit, public_inputs)?;
     
     Ok(Proof {
         bytes: proof_bytes,
-        public_inputs: pi_bytes,
+        public_inputs: encode_public_inputs(public_inputs),
     })
 }
 
+/// Flattened instance values as stored on `Proof`.
+fn encode_public_inputs(public_inputs: &[&[Fr]]) -> Vec<encoding::SerField> {
+    public_inputs.iter()
+        .flat_map(|arr| arr.iter())
+        .map(|fr| encoding::SerField(*fr))
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum BatchProofResult {
+    Proved(Proof),
+    Failed(CircuitError),
+    Cancelled,
+}
+
+/// Proves each `(circuit, instance columns)` item one after another on the
+/// calling thread; items are not proved concurrently. `cancel` is checked
+/// before every item; once set, the remaining items are reported as
+/// `Cancelled`. A proof that has already started is not interrupted, so
+/// cancellation takes effect at the next item boundary. Callers that signal
+/// from another thread can share the flag as an `Arc<AtomicBool>` and pass
+/// `&flag`.
+pub fn prove_withdrawal_batch_sequential<I>(
+    prover: &ProverParams,
+    items: I,
+    cancel: &AtomicBool,
+) -> Vec<BatchProofResult>
+where
+    I: IntoIterator<Item = (WithdrawalCircuit<Fr>, Vec<Vec<Fr>>)>,
+{
+    items
+        .into_iter()
+        .map(|(circuit, instances)| {
+            if cancel.load(Ordering::Acquire) {
+                return BatchProofResult::Cancelled;
+            }
+
+            let columns: Vec<&[Fr]> = instances.iter().map(|c| c.as_slice()).collect();
+            match prove_withdrawal(prover, circuit, &columns) {
+                Ok(proof) => BatchProofResult::Proved(proof),
+                Err(e) => BatchProofResult::Failed(e),
+            }
+        })
+        .collect()
+}
+
 pub fn verify_withdrawal(
     verifier: &VerifierParams,
     proof: &Proof,
     public_inputs: &[&[Fr]],
 ) -> Result<bool, CircuitError> {
-    let mut transcript = Blake2bRead::<_, G1Affine, Challenge255<_>>::init(&proof.bytes[..]);
-    
-    let strategy = SingleStrategy::new(&verifier.params);
-    
-    verify_proof::<KZGCommitmentScheme<Bn256>, VerifierSHPLONK<'_, Bn256>, _, _, _>(
-        &verifier.params,
-        &verifier.vk,
-        strategy,
-        &[public_inputs],
-        &mut transcript,
-    )
-    .map_err(|e| CircuitError::ProofVerification(format!("Verification failed: {:?}", e)))?;
-    
-    Ok(true)
+    verify_withdrawal_with(&verifier.backend(), proof, public_inputs)
+}
+
+pub fn verify_withdrawal_with<B: ProvingBackend<WithdrawalCircuit<Fr>>>(
+    backend: &B,
+    proof: &Proof,
+    public_inputs: &[&[Fr]],
+) -> Result<bool, CircuitError> {
+    check_instance_shape(&WithdrawalCircuit::<Fr>::num_instances(), public_inputs)?;
+    // The proof records the inputs it was made for; a different set cannot
+    // verify, so skip the pairing work.
+    if encode_public_inputs(public_inputs) != proof.public_inputs {
+        return Ok(false);
+    }
+    backend.verify(&proof.bytes, public_inputs)
+}
+
+/// Verifies a withdrawal at the vault `vault_address` on `chain_id`. The
+/// deployment fields come from the verifier, not from `public_inputs`, so a
+/// proof bound to any other vault or chain is rejected.
+pub fn verify_withdrawal_at(
+    verifier: &VerifierParams,
+    proof: &Proof,
+    public_inputs: &withdrawal_circuit::WithdrawalPublicInputs,
+    vault_address: [u8; 20],
+    chain_id: u64,
+) -> Result<bool, CircuitError> {
+    let instances = public_inputs.clone().bound_to(vault_address, chain_id).instances::<Fr>()?;
+    verify_withdrawal(verifier, proof, &[&instances])
+}
+
+/// Like `verify_withdrawal`, for public inputs received as raw little-endian
+/// bytes. Encodings at or above the field modulus are rejected rather than
+/// reduced, so each instance value has exactly one accepted byte form.
+pub fn verify_withdrawal_bytes(
+    verifier: &VerifierParams,
+    proof: &Proof,
+    public_inputs: &[[u8; 32]],
+) -> Result<bool, CircuitError> {
+    let instances = decode_public_inputs(public_inputs)?;
+    verify_withdrawal(verifier, proof, &[&instances])
+}
+
+pub fn decode_public_inputs(public_inputs: &[[u8; 32]]) -> Result<Vec<Fr>, CircuitError> {
+    public_inputs
+        .iter()
+        .enumerate()
+        .map(|(i, bytes)| {
+            encoding::field_from_canonical_bytes::<Fr>(bytes).ok_or_else(|| {
+                CircuitError::InvalidInput(format!("Public input {} is not a canonical field element", i))
+            })
+        })
+        .collect()
+}
+
+pub fn verify_link(
+    verifier: &VerifierParams,
+    proof: &Proof,
+    public_inputs: &LinkPublicInputs,
+) -> Result<bool, CircuitError> {
+    let instances = public_inputs.instances()?;
+    check_instance_shape(&LinkCircuit::<Fr>::num_instances(), &[&instances])?;
+    verify_circuit_proof(verifier, proof, &[&instances])
+}
+
+/// Rejects instance vectors whose column count or per-column lengths differ
+/// from what the circuit declares, before handing them to the halo2 verifier.
+pub fn check_instance_shape(expected: &[usize], public_inputs: &[&[Fr]]) -> Result<(), CircuitError> {
+    let actual: Vec<usize> = public_inputs.iter().map(|column| column.len()).collect();
+    if actual != expected {
+        return Err(CircuitError::InvalidInput(format!(
+            "Instance shape mismatch: expected {} column(s) with lengths {:?}, got {} column(s) with lengths {:?}",
+            expected.len(),
+            expected,
+            actual.len(),
+            actual
+        )));
+    }
+    Ok(())
+}
+
+fn verify_circuit_proof(
+    verifier: &VerifierParams,
+    proof: &Proof,
+    public_inputs: &[&[Fr]],
+) -> Result<bool, CircuitError> {
+    verifier.backend().verify(&proof.bytes, public_inputs)
 }
 
 #[cfg(test)]
@@ -140,7 +318,7 @@ mod tests {
     fn test_proof_serialization() {
         let proof = Proof {
             bytes: vec![1, 2, 3, 4, 5],
-            public_inputs: vec![[42u8; 32]],
+            public_inputs: vec![encoding::SerField(Fr::from(42u64))],
         };
         
         let serialized = proof.to_bytes();
@@ -149,4 +327,164 @@ mod tests {
         assert_eq!(proof.bytes, deserialized.bytes);
         assert_eq!(proof.public_inputs, deserialized.public_inputs);
     }
+
+    #[test]
+    fn test_deserialized_proof_verifies() {
+        let (prover, verifier) = setup_withdrawal_circuit(11).unwrap();
+        let witness = withdrawal_circuit::WithdrawalWitness {
+            secret: [6u8; 32],
+            merkle_path: vec![[0u8; 32]; withdrawal_circuit::MERKLE_DEPTH],
+            path_indices: vec![false; withdrawal_circuit::MERKLE_DEPTH],
+            ..Default::default()
+        };
+        let public_inputs = witness.public_inputs::<Fr>([0xab; 20]);
+        let instances = public_inputs.instances::<Fr>().unwrap();
+        let proof = prove_withdrawal(&prover, WithdrawalCircuit::new(witness, public_inputs), &[&instances]).unwrap();
+
+        let serialized = proof.to_bytes();
+        let deserialized = Proof::from_bytes(&serialized).unwrap();
+        assert!(verify_withdrawal(&verifier, &deserialized, &[&instances]).unwrap());
+
+        // One flipped byte inside the proof transcript, carried through the
+        // same encoding, must not verify.
+        let mut tampered = deserialized.clone();
+        let middle = tampered.bytes.len() / 2;
+        tampered.bytes[middle] ^= 1;
+        let tampered = Proof::from_bytes(&tampered.to_bytes()).unwrap();
+        assert!(!matches!(verify_withdrawal(&verifier, &tampered, &[&instances]), Ok(true)));
+    }
+
+    #[test]
+    fn test_instance_shape_validation() {
+        let (prover, verifier) = setup_withdrawal_circuit(11).unwrap();
+        let witness = withdrawal_circuit::WithdrawalWitness {
+            merkle_path: vec![[0u8; 32]; withdrawal_circuit::MERKLE_DEPTH],
+            path_indices: vec![false; withdrawal_circuit::MERKLE_DEPTH],
+            ..Default::default()
+        };
+        let public_inputs = witness.public_inputs::<Fr>([0xab; 20]);
+        let instances = public_inputs.instances::<Fr>().unwrap();
+        let proof = prove_withdrawal(&prover, WithdrawalCircuit::new(witness, public_inputs), &[&instances]).unwrap();
+
+        let too_few: &[&[Fr]] = &[];
+        let err = verify_withdrawal(&verifier, &proof, too_few).unwrap_err();
+        assert!(matches!(err, CircuitError::InvalidInput(ref msg) if msg.contains("expected 1 column")));
+
+        let too_many: &[&[Fr]] = &[&instances, &[]];
+        let err = verify_withdrawal(&verifier, &proof, too_many).unwrap_err();
+        assert!(matches!(err, CircuitError::InvalidInput(ref msg) if msg.contains("got 2 column")));
+
+        let too_long = [instances.as_slice(), &[Fr::from(1u64)]].concat();
+        assert!(matches!(verify_withdrawal(&verifier, &proof, &[&too_long]), Err(CircuitError::InvalidInput(_))));
+
+        assert!(verify_withdrawal(&verifier, &proof, &[&instances]).unwrap());
+
+        let mut bytes: Vec<[u8; 32]> = instances.iter().map(|fr| encoding::field_to_bytes(*fr)).collect();
+        assert!(verify_withdrawal_bytes(&verifier, &proof, &bytes).unwrap());
+
+        // p + k for the root, which would reduce to k if it were accepted.
+        let mut modulus_plus_k = encoding::field_to_bytes(-Fr::from(1u64));
+        modulus_plus_k[0] += 1 + 7;
+        bytes[0] = modulus_plus_k;
+        assert!(matches!(verify_withdrawal_bytes(&verifier, &proof, &bytes), Err(CircuitError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_proof_for_one_vault_fails_at_another() {
+        let (prover, verifier) = setup_withdrawal_circuit(11).unwrap();
+        let witness = withdrawal_circuit::WithdrawalWitness {
+            secret: [5u8; 32],
+            merkle_path: vec![],
+            path_indices: vec![],
+            ..Default::default()
+        };
+        let vault_a = [0xa0; 20];
+        let public_inputs = witness.public_inputs::<Fr>([0xab; 20]).bound_to(vault_a, 1);
+        let instances = public_inputs.instances::<Fr>().unwrap();
+        let proof = prove_withdrawal(&prover, WithdrawalCircuit::new(witness, public_inputs.clone()), &[&instances]).unwrap();
+
+        assert!(verify_withdrawal_at(&verifier, &proof, &public_inputs, vault_a, 1).unwrap());
+        assert!(!verify_withdrawal_at(&verifier, &proof, &public_inputs, [0xb0; 20], 1).unwrap());
+        assert!(!verify_withdrawal_at(&verifier, &proof, &public_inputs, vault_a, 10).unwrap());
+
+        // Relabelling the recorded inputs does not help: the pairing check
+        // still fails for the other deployment.
+        let vault_b = public_inputs.clone().bound_to([0xb0; 20], 1).instances::<Fr>().unwrap();
+        let mut relabelled = proof.clone();
+        relabelled.public_inputs = vault_b.iter().map(|fr| encoding::SerField(*fr)).collect();
+        assert!(!matches!(verify_withdrawal_at(&verifier, &relabelled, &public_inputs, [0xb0; 20], 1), Ok(true)));
+    }
+
+    #[test]
+    fn test_decode_public_inputs_rejects_non_canonical() {
+        let canonical = [encoding::field_to_bytes(-Fr::from(1u64)), [0u8; 32]];
+        assert_eq!(decode_public_inputs(&canonical).unwrap(), vec![-Fr::from(1u64), Fr::from(0u64)]);
+
+        let mut modulus_plus_k = canonical[0];
+        modulus_plus_k[0] += 1 + 3;
+        assert!(matches!(
+            decode_public_inputs(&[canonical[1], modulus_plus_k]),
+            Err(CircuitError::InvalidInput(ref msg)) if msg.contains("Public input 1")
+        ));
+    }
+
+    #[test]
+    fn test_tampered_public_nullifier_fails_verification() {
+        let (prover, verifier) = setup_withdrawal_circuit(11).unwrap();
+        let witness = withdrawal_circuit::WithdrawalWitness {
+            secret: [3u8; 32],
+            merkle_path: vec![[0u8; 32]; withdrawal_circuit::MERKLE_DEPTH],
+            path_indices: vec![false; withdrawal_circuit::MERKLE_DEPTH],
+            ..Default::default()
+        };
+        let public_inputs = witness.public_inputs::<Fr>([0xab; 20]);
+        let instances = public_inputs.instances::<Fr>().unwrap();
+        let proof = prove_withdrawal(&prover, WithdrawalCircuit::new(witness, public_inputs), &[&instances]).unwrap();
+        assert!(verify_withdrawal(&verifier, &proof, &[&instances]).unwrap());
+
+        let mut tampered = instances.clone();
+        tampered[1] += Fr::from(1u64);
+        assert!(!verify_withdrawal(&verifier, &proof, &[&tampered]).unwrap());
+        // Also with the recorded inputs rewritten to match, so the pairing
+        // check itself has to reject it.
+        let mut relabelled = proof.clone();
+        relabelled.public_inputs[1] = encoding::SerField(tampered[1]);
+        assert!(!matches!(verify_withdrawal(&verifier, &relabelled, &[&tampered]), Ok(true)));
+    }
+
+    #[test]
+    fn test_batch_prover_cancellation() {
+        use std::sync::Arc;
+
+        let (prover, verifier) = setup_withdrawal_circuit(11).unwrap();
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        let items = (0..3).map(|i| {
+            if i == 1 {
+                cancel.store(true, Ordering::Release);
+            }
+            let witness = withdrawal_circuit::WithdrawalWitness {
+                merkle_path: vec![[0u8; 32]; withdrawal_circuit::MERKLE_DEPTH],
+                path_indices: vec![false; withdrawal_circuit::MERKLE_DEPTH],
+                ..Default::default()
+            };
+            let public_inputs = witness.public_inputs::<Fr>([0xab; 20]);
+            let instances = public_inputs.instances::<Fr>().unwrap();
+            let circuit = WithdrawalCircuit::<Fr>::new(witness, public_inputs);
+            (circuit, vec![instances])
+        });
+
+        let results = prove_withdrawal_batch_sequential(&prover, items, &cancel);
+
+        assert_eq!(results.len(), 3);
+        match &results[0] {
+            BatchProofResult::Proved(proof) => {
+                let instances: Vec<Fr> = proof.public_inputs.iter().map(|value| value.0).collect();
+                assert!(verify_withdrawal(&verifier, proof, &[&instances]).unwrap());
+            }
+            other => panic!("expected first item to be proved, got {:?}", other),
+        }
+        assert!(matches!(results[1], BatchProofResult::Cancelled));
+        assert!(matches!(results[2], BatchProofResult::Cancelled));
+    }
 }
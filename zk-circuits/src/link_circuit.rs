@@ -0,0 +1,273 @@
+use std::marker::PhantomData;
+use ff::PrimeField;
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    halo2curves::bn256::Fr,
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Instance},
+};
+use serde::{Serialize, Deserialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::commitment::commitment_bytes;
+use crate::encoding::{field_from_bytes, field_from_canonical_bytes};
+use crate::poseidon::{PoseidonChip, PoseidonConfig};
+use crate::CircuitError;
+
+#[derive(Clone, Debug)]
+pub struct LinkConfig {
+    pub advice: [Column<Advice>; 3],
+    pub instance: Column<Instance>,
+    pub poseidon: PoseidonConfig,
+}
+
+/// Private inputs: one secret shared by both deposits, each with its own
+/// nullifier seed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct LinkWitness {
+    pub secret: [u8; 32],
+    pub nullifier_seed_a: [u8; 32],
+    pub nullifier_seed_b: [u8; 32],
+}
+
+/// The two commitments, in the canonical encoding produced by
+/// `commitment::commitment_bytes`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkPublicInputs {
+    pub commitment_a: [u8; 32],
+    pub commitment_b: [u8; 32],
+}
+
+impl LinkWitness {
+    pub fn new(secret: [u8; 32], nullifier_seed_a: [u8; 32], nullifier_seed_b: [u8; 32]) -> Self {
+        Self {
+            secret,
+            nullifier_seed_a,
+            nullifier_seed_b,
+        }
+    }
+
+    pub fn public_inputs(&self) -> LinkPublicInputs {
+        LinkPublicInputs {
+            commitment_a: commitment_bytes(&self.secret, &self.nullifier_seed_a),
+            commitment_b: commitment_bytes(&self.secret, &self.nullifier_seed_b),
+        }
+    }
+}
+
+impl LinkPublicInputs {
+    pub fn instances(&self) -> Result<Vec<Fr>, CircuitError> {
+        [self.commitment_a, self.commitment_b]
+            .iter()
+            .map(|bytes| {
+                field_from_canonical_bytes::<Fr>(bytes).ok_or_else(|| {
+                    CircuitError::InvalidInput("Commitment is not a canonical field element".into())
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LinkCircuit<F: PrimeField> {
+    pub witness: Option<LinkWitness>,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> Default for LinkCircuit<F> {
+    fn default() -> Self {
+        Self {
+            witness: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: PrimeField> LinkCircuit<F> {
+    pub fn new(witness: LinkWitness) -> Self {
+        Self {
+            witness: Some(witness),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Number of values expected in each instance column.
+    pub fn num_instances() -> Vec<usize> {
+        vec![2]
+    }
+}
+
+impl<F: PrimeField> Circuit<F> for LinkCircuit<F> {
+    type Config = LinkConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let constants = meta.fixed_column();
+        let instance = meta.instance_column();
+
+        meta.enable_constant(constants);
+        meta.enable_equality(instance);
+        for col in advice.iter() {
+            meta.enable_equality(*col);
+        }
+
+        let round_constants = [meta.fixed_column(), meta.fixed_column(), meta.fixed_column()];
+        let poseidon = PoseidonChip::configure(meta, advice, round_constants);
+
+        LinkConfig {
+            advice,
+            instance,
+            poseidon,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let witness = self.witness.as_ref();
+
+        let (secret, seed_a, seed_b) = layouter.assign_region(
+            || "link_inputs",
+            |mut region| {
+                let secret = region.assign_advice(
+                    || "secret",
+                    config.advice[0],
+                    0,
+                    || witness.map(|w| Value::known(field_from_bytes::<F>(&w.secret))).unwrap_or(Value::unknown()),
+                )?;
+
+                let seed_a = region.assign_advice(
+                    || "nullifier_seed_a",
+                    config.advice[1],
+                    0,
+                    || witness.map(|w| Value::known(field_from_bytes::<F>(&w.nullifier_seed_a))).unwrap_or(Value::unknown()),
+                )?;
+
+                let seed_b = region.assign_advice(
+                    || "nullifier_seed_b",
+                    config.advice[2],
+                    0,
+                    || witness.map(|w| Value::known(field_from_bytes::<F>(&w.nullifier_seed_b))).unwrap_or(Value::unknown()),
+                )?;
+
+                Ok((secret, seed_a, seed_b))
+            },
+        )?;
+
+        // Both commitments hash the same secret cell, so they can only match
+        // the public pair if one secret opens both.
+        let poseidon = PoseidonChip::<F>::construct(config.poseidon.clone());
+        let commitment_a = poseidon.hash(layouter.namespace(|| "commitment_a"), &[secret.clone(), seed_a])?;
+        let commitment_b = poseidon.hash(layouter.namespace(|| "commitment_b"), &[secret, seed_b])?;
+
+        layouter.constrain_instance(commitment_a.cell(), config.instance, 0)?;
+        layouter.constrain_instance(commitment_b.cell(), config.instance, 1)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ff::Field;
+    use halo2_proofs::dev::MockProver;
+    use crate::commitment::generate_deposit;
+    use crate::encoding::field_to_bytes;
+
+    const LINK_TEST_K: u32 = 8;
+
+    fn sample_witness() -> LinkWitness {
+        let mut secret = [0u8; 32];
+        secret[..4].copy_from_slice(&[1, 2, 3, 4]);
+        let mut seed_a = [0u8; 32];
+        seed_a[0] = 5;
+        let mut seed_b = [0u8; 32];
+        seed_b[0] = 6;
+        LinkWitness::new(secret, seed_a, seed_b)
+    }
+
+    #[test]
+    fn test_link_same_owner() {
+        let witness = sample_witness();
+        let instances = witness.public_inputs().instances().unwrap();
+
+        let circuit = LinkCircuit::<Fr>::new(witness);
+        let prover = MockProver::run(LINK_TEST_K, &circuit, vec![instances]).unwrap();
+        prover.verify().unwrap();
+    }
+
+    #[test]
+    fn test_link_rejects_different_secrets() {
+        let witness = sample_witness();
+        let mut other = witness.clone();
+        other.secret[0] ^= 0xff;
+
+        let forged = LinkPublicInputs {
+            commitment_a: witness.public_inputs().commitment_a,
+            commitment_b: other.public_inputs().commitment_b,
+        };
+
+        let circuit = LinkCircuit::<Fr>::new(witness);
+        let prover = MockProver::run(LINK_TEST_K, &circuit, vec![forged.instances().unwrap()]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_link_native_prove_and_verify() {
+        let (prover, verifier) = crate::setup_link_circuit(LINK_TEST_K).unwrap();
+        let witness = sample_witness();
+        let public_inputs = witness.public_inputs();
+
+        let proof = crate::prove_link(&prover, witness, &public_inputs).unwrap();
+        assert!(crate::verify_link(&verifier, &proof, &public_inputs).unwrap());
+
+        let mut wrong = public_inputs.clone();
+        wrong.commitment_b = wrong.commitment_a;
+        assert!(crate::verify_link(&verifier, &proof, &wrong).is_err());
+    }
+
+    /// The old quadratic gate accepted any (secret, seed) pair on the curve
+    /// `s^2 + 2n^2 + 3 = c`. Reusing deposit A's secret and solving that curve
+    /// for a seed would "link" A to an unrelated deposit B about half the
+    /// time. The Poseidon commitment has no such solution to find.
+    #[test]
+    fn test_link_rejects_forged_seed_for_unrelated_commitment() {
+        let two = Fr::from(2u64);
+        let three = Fr::from(3u64);
+
+        let (a, b, forged_seed) = std::iter::repeat_with(|| (generate_deposit(), generate_deposit()))
+            .find_map(|(a, b)| {
+                let s = field_from_bytes::<Fr>(&a.secret);
+                let c_b = field_from_canonical_bytes::<Fr>(&b.commitment).unwrap();
+                let seed: Option<Fr> = ((c_b - s * s - three) * two.invert().unwrap()).sqrt().into();
+                seed.map(|seed| (a, b, seed))
+            })
+            .unwrap();
+
+        let s = field_from_bytes::<Fr>(&a.secret);
+        let c_b = field_from_canonical_bytes::<Fr>(&b.commitment).unwrap();
+        assert_eq!(s * s + forged_seed * forged_seed * two + three, c_b);
+
+        let forged = LinkWitness::new(a.secret, a.nullifier_seed, field_to_bytes(forged_seed));
+        let claimed = LinkPublicInputs {
+            commitment_a: a.commitment,
+            commitment_b: b.commitment,
+        };
+        assert_eq!(claimed.commitment_a, commitment_bytes(&a.secret, &a.nullifier_seed));
+
+        let circuit = LinkCircuit::<Fr>::new(forged);
+        let prover = MockProver::run(LINK_TEST_K, &circuit, vec![claimed.instances().unwrap()]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
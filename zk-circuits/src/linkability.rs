@@ -0,0 +1,159 @@
+//! Privacy invariants between deposits and withdrawals.
+//!
+//! A deposit publishes its commitment and its leaf position. A withdrawal
+//! publishes the root, the nullifier, the recipient and the amount. For every
+//! set of deposits and matching withdrawals, these tests check that:
+//!
+//! - no nullifier can be computed from any deposit's public data (its
+//!   commitment, leaf node or position), so spending doesn't point at the leaf;
+//! - nullifiers never repeat and never equal a commitment;
+//! - deposits that share a secret still get unrelated nullifiers;
+//! - the nullifier depends only on the spend secret and the position, so the
+//!   recipient and amount chosen at withdrawal time aren't tied back to it;
+//! - the compliance proof's `commitment_hash` and the withdrawal's recipient
+//!   hash can't be computed from any deposit's public data either.
+//!
+//! Commitments, nullifiers and the compliance `commitment_hash` are all
+//! Poseidon hashes over at least one deposit secret, so these invariants rest
+//! on Poseidon's preimage resistance rather than on the derivations alone.
+
+use std::collections::HashSet;
+use halo2_proofs::halo2curves::bn256::Fr;
+use sha2::{Sha256, Digest};
+
+use crate::association_circuit::compliance_commitment_hash;
+use crate::commitment::{commitment_bytes, generate_deposit, Deposit};
+use crate::encoding::{field_from_canonical_bytes, field_to_bytes};
+use crate::merkle::{leaf_hash, PoseidonMerkleTree};
+use crate::poseidon::poseidon_hash_native;
+use crate::withdrawal_circuit::{compute_nullifier, WithdrawalPublicInputs, MERKLE_DEPTH};
+
+const DEPOSITS: usize = 16;
+
+fn deposit_pool() -> (PoseidonMerkleTree, Vec<Deposit>) {
+    let deposits: Vec<Deposit> = (0..DEPOSITS).map(|_| generate_deposit()).collect();
+    let leaves: Vec<[u8; 32]> = deposits.iter().map(|d| d.commitment).collect();
+
+    let mut tree = PoseidonMerkleTree::empty(MERKLE_DEPTH);
+    tree.insert_batch(0, &leaves).unwrap();
+    (tree, deposits)
+}
+
+fn withdraw(tree: &PoseidonMerkleTree, index: usize, deposit: &Deposit, recipient: [u8; 20], amount: u64) -> WithdrawalPublicInputs {
+    tree.withdrawal_witness(index as u32, deposit.secret, deposit.nullifier_seed, amount)
+        .unwrap()
+        .public_inputs::<Fr>(recipient)
+}
+
+/// Everything an observer can derive from a deposit's public data alone.
+fn public_derivations(tree: &PoseidonMerkleTree, index: usize, commitment: &[u8; 32]) -> Vec<[u8; 32]> {
+    let position = (index as u64).to_le_bytes();
+    let commitment_field = field_from_canonical_bytes::<Fr>(commitment).unwrap();
+
+    vec![
+        *commitment,
+        tree.leaf(index),
+        leaf_hash(commitment),
+        Sha256::digest(commitment).into(),
+        Sha256::new().chain_update(commitment).chain_update(position).finalize().into(),
+        field_to_bytes(compute_nullifier(commitment_field, Fr::from(index as u64))),
+    ]
+}
+
+#[test]
+fn test_nullifier_not_derivable_from_deposit_data() {
+    let (tree, deposits) = deposit_pool();
+
+    let derivable: HashSet<[u8; 32]> = deposits
+        .iter()
+        .enumerate()
+        .flat_map(|(index, deposit)| public_derivations(&tree, index, &deposit.commitment))
+        .collect();
+
+    for (index, deposit) in deposits.iter().enumerate() {
+        let withdrawal = withdraw(&tree, index, deposit, [0xab; 20], 1);
+        assert!(!derivable.contains(&withdrawal.nullifier), "nullifier of deposit {} is derivable", index);
+    }
+}
+
+#[test]
+fn test_nullifiers_unique_and_distinct_from_commitments() {
+    let (tree, deposits) = deposit_pool();
+    let commitments: HashSet<[u8; 32]> = deposits.iter().map(|d| d.commitment).collect();
+
+    let mut nullifiers = HashSet::new();
+    for (index, deposit) in deposits.iter().enumerate() {
+        let withdrawal = withdraw(&tree, index, deposit, [0xab; 20], 1);
+        assert!(!commitments.contains(&withdrawal.nullifier));
+        assert!(nullifiers.insert(withdrawal.nullifier), "nullifier of deposit {} repeats", index);
+    }
+}
+
+#[test]
+fn test_shared_secret_deposits_get_unrelated_nullifiers() {
+    let first = generate_deposit();
+    let second_seed = generate_deposit().nullifier_seed;
+    let second = Deposit {
+        secret: first.secret,
+        nullifier_seed: second_seed,
+        commitment: commitment_bytes(&first.secret, &second_seed),
+    };
+
+    let mut tree = PoseidonMerkleTree::empty(MERKLE_DEPTH);
+    tree.insert_batch(0, &[first.commitment, second.commitment]).unwrap();
+
+    let a = withdraw(&tree, 0, &first, [0xab; 20], 1);
+    let b = withdraw(&tree, 1, &second, [0xab; 20], 1);
+    assert_ne!(a.nullifier, b.nullifier);
+    assert!(!public_derivations(&tree, 0, &first.commitment).contains(&b.nullifier));
+    assert!(!public_derivations(&tree, 1, &second.commitment).contains(&a.nullifier));
+}
+
+#[test]
+fn test_nullifier_independent_of_recipient_and_amount() {
+    let (tree, deposits) = deposit_pool();
+
+    let to_alice = withdraw(&tree, 3, &deposits[3], [0xaa; 20], 1);
+    let to_bob = withdraw(&tree, 3, &deposits[3], [0xbb; 20], 2);
+
+    assert_eq!(to_alice.nullifier, to_bob.nullifier);
+    assert_eq!(to_alice.merkle_root, to_bob.merkle_root);
+    assert_ne!(to_alice.instances::<Fr>().unwrap(), to_bob.instances::<Fr>().unwrap());
+}
+
+#[test]
+fn test_compliance_commitment_hash_not_derivable_from_deposit_data() {
+    let (tree, deposits) = deposit_pool();
+
+    let derivable: HashSet<[u8; 32]> = deposits
+        .iter()
+        .enumerate()
+        .flat_map(|(index, deposit)| public_derivations(&tree, index, &deposit.commitment))
+        .collect();
+
+    let mut hashes = HashSet::new();
+    for (index, deposit) in deposits.iter().enumerate() {
+        let commitment_hash = compliance_commitment_hash(&deposit.commitment, &deposit.secret);
+        assert!(!derivable.contains(&commitment_hash), "commitment_hash of deposit {} is derivable", index);
+        assert!(hashes.insert(commitment_hash), "commitment_hash of deposit {} repeats", index);
+    }
+}
+
+#[test]
+fn test_recipient_hash_not_derivable_from_deposit_data() {
+    let (tree, deposits) = deposit_pool();
+
+    let derivable: HashSet<[u8; 32]> = deposits
+        .iter()
+        .enumerate()
+        .flat_map(|(index, deposit)| public_derivations(&tree, index, &deposit.commitment))
+        .collect();
+
+    for (index, deposit) in deposits.iter().enumerate() {
+        let withdrawal = withdraw(&tree, index, deposit, [0xab; 20], 1);
+        let instances = withdrawal.instances::<Fr>().unwrap();
+        // Poseidon over the recipient and amount instances.
+        let recipient_hash = field_to_bytes(poseidon_hash_native(&instances[2..4]));
+        assert!(!derivable.contains(&recipient_hash), "recipient hash of deposit {} is derivable", index);
+    }
+}
@@ -1,8 +1,23 @@
+use std::collections::{HashMap, HashSet};
+use std::marker::PhantomData;
+use halo2_proofs::halo2curves::bn256::Fr;
 use sha2::{Sha256, Digest};
 use serde::{Serialize, Deserialize};
 
+use crate::commitment::commitment_bytes;
+use crate::encoding::{field_from_bytes, field_to_bytes};
+use crate::poseidon::poseidon_hash_native;
+use crate::withdrawal_circuit::WithdrawalWitness;
+use crate::CircuitError;
+
 pub const MERKLE_TREE_DEPTH: usize = 20;
 
+/// Prefix for hashing a leaf value into the bottom level of the tree.
+pub const LEAF_DOMAIN: u8 = 0x00;
+/// Prefix for hashing two children into an internal node. Distinct from
+/// `LEAF_DOMAIN` so an internal node can never be replayed as a leaf.
+pub const NODE_DOMAIN: u8 = 0x01;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MerkleProof {
     pub path: Vec<[u8; 32]>,
@@ -10,23 +25,173 @@ pub struct MerkleProof {
     pub root: [u8; 32],
 }
 
-pub struct MerkleTree {
+/// How a `MerkleTree` turns leaves and child pairs into nodes. Empty slots
+/// are the all-zero value at the bottom level under every hasher.
+pub trait MerkleHasher {
+    /// Bottom-level node stored for an inserted leaf.
+    fn leaf(leaf: &[u8; 32]) -> [u8; 32];
+    fn node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32];
+}
+
+/// Domain-separated SHA-256: `leaf_hash` at the bottom, `merkle_hash` above.
+pub struct Sha256Hasher;
+
+impl MerkleHasher for Sha256Hasher {
+    fn leaf(leaf: &[u8; 32]) -> [u8; 32] {
+        leaf_hash(leaf)
+    }
+
+    fn node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+        merkle_hash(left, right)
+    }
+}
+
+/// The tree the withdrawal circuit proves membership in: the commitment is
+/// the leaf itself and each parent is `Poseidon(left, right)`, so its empty
+/// subtrees are `withdrawal_circuit::zero_hashes`.
+pub struct PoseidonHasher;
+
+impl MerkleHasher for PoseidonHasher {
+    fn leaf(leaf: &[u8; 32]) -> [u8; 32] {
+        *leaf
+    }
+
+    fn node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+        field_to_bytes(poseidon_hash_native(&[field_from_bytes::<Fr>(left), field_from_bytes::<Fr>(right)]))
+    }
+}
+
+pub type PoseidonMerkleTree = MerkleTree<PoseidonHasher>;
+
+pub struct MerkleTree<H: MerkleHasher = Sha256Hasher> {
     depth: usize,
     zero_values: Vec<[u8; 32]>,
+    nodes: HashMap<(usize, usize), [u8; 32]>,
+    /// Raw leaf at each occupied index, and the reverse lookup used to
+    /// refuse a commitment that is already in the tree.
+    leaves: HashMap<usize, [u8; 32]>,
+    leaf_indices: HashMap<[u8; 32], usize>,
+    _hasher: PhantomData<H>,
 }
 
 impl MerkleTree {
     pub fn new(depth: usize) -> Self {
+        Self::empty(depth)
+    }
+}
+
+impl<H: MerkleHasher> MerkleTree<H> {
+    /// An empty tree of `depth` levels under hasher `H`.
+    pub fn empty(depth: usize) -> Self {
         let mut zero_values = Vec::with_capacity(depth + 1);
         let mut current = [0u8; 32];
         zero_values.push(current);
         
         for _ in 0..depth {
-            current = merkle_hash(&current, &current);
+            current = H::node(&current, &current);
             zero_values.push(current);
         }
         
-        Self { depth, zero_values }
+        Self {
+            depth,
+            zero_values,
+            nodes: HashMap::new(),
+            leaves: HashMap::new(),
+            leaf_indices: HashMap::new(),
+            _hasher: PhantomData,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn capacity(&self) -> usize {
+        1usize.checked_shl(self.depth as u32).unwrap_or(usize::MAX)
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.node(self.depth, 0)
+    }
+
+    pub fn insert(&mut self, index: usize, leaf: [u8; 32]) -> Result<[u8; 32], CircuitError> {
+        self.insert_batch(index, &[leaf])
+    }
+
+    /// Writes `leaves` at `start_index..` and then rehashes every affected
+    /// internal node exactly once, level by level. Produces the same root as
+    /// inserting the leaves one at a time.
+    ///
+    /// A leaf that is already stored at another index, or that appears twice
+    /// in `leaves`, is rejected before anything is written, so the tree and
+    /// its root are unchanged on error.
+    pub fn insert_batch(&mut self, start_index: usize, leaves: &[[u8; 32]]) -> Result<[u8; 32], CircuitError> {
+        if leaves.is_empty() {
+            return Ok(self.root());
+        }
+
+        let end_index = start_index
+            .checked_add(leaves.len())
+            .filter(|end| *end <= self.capacity())
+            .ok_or_else(|| CircuitError::InvalidInput(format!(
+                "Leaves {}..{} exceed tree capacity {}",
+                start_index,
+                start_index.saturating_add(leaves.len()),
+                self.capacity()
+            )))?;
+
+        let mut batch = HashSet::with_capacity(leaves.len());
+        for (offset, leaf) in leaves.iter().enumerate() {
+            let index = start_index + offset;
+            if let Some(&existing) = self.leaf_indices.get(leaf).filter(|&&at| at != index) {
+                return Err(CircuitError::InvalidInput(format!(
+                    "Commitment for leaf {} is already in the tree at leaf {}",
+                    index, existing
+                )));
+            }
+            if !batch.insert(leaf) {
+                return Err(CircuitError::InvalidInput(format!(
+                    "Commitment for leaf {} appears more than once in the batch",
+                    index
+                )));
+            }
+        }
+
+        for (offset, leaf) in leaves.iter().enumerate() {
+            let index = start_index + offset;
+            if let Some(replaced) = self.leaves.insert(index, *leaf) {
+                self.leaf_indices.remove(&replaced);
+            }
+            self.leaf_indices.insert(*leaf, index);
+            self.nodes.insert((0, index), H::leaf(leaf));
+        }
+
+        let mut first = start_index;
+        let mut last = end_index - 1;
+        for level in 0..self.depth {
+            first >>= 1;
+            last >>= 1;
+            for parent in first..=last {
+                let left = self.node(level, parent << 1);
+                let right = self.node(level, (parent << 1) | 1);
+                self.nodes.insert((level + 1, parent), H::node(&left, &right));
+            }
+        }
+
+        Ok(self.root())
+    }
+
+    /// The bottom-level node at `index`: `H::leaf` of the inserted leaf, or
+    /// the all-zero empty value.
+    pub fn leaf(&self, index: usize) -> [u8; 32] {
+        self.node(0, index)
+    }
+
+    fn node(&self, level: usize, index: usize) -> [u8; 32] {
+        self.nodes
+            .get(&(level, index))
+            .copied()
+            .unwrap_or(self.zero_values[level])
     }
 
     pub fn compute_root_from_path(
@@ -35,24 +200,24 @@ impl MerkleTree {
         path: &[[u8; 32]],
         indices: &[bool],
     ) -> [u8; 32] {
-        let mut current = *leaf;
-        
-        for (sibling, &is_right) in path.iter().zip(indices.iter()) {
-            if is_right {
-                current = merkle_hash(sibling, &current);
-            } else {
-                current = merkle_hash(&current, sibling);
-            }
-        }
-        
-        current
+        root_from_path::<H>(leaf, path, indices)
     }
 
+    /// Sibling path and path bits for `leaf`, which must be the leaf inserted
+    /// at `index`; an empty slot or a different leaf is an error rather than a
+    /// path that cannot fold to the root.
     pub fn generate_proof_for_leaf(
         &self,
-        _leaf: &[u8; 32],
+        leaf: &[u8; 32],
         index: usize,
-    ) -> (Vec<[u8; 32]>, Vec<bool>) {
+    ) -> Result<(Vec<[u8; 32]>, Vec<bool>), CircuitError> {
+        if self.leaves.get(&index) != Some(leaf) {
+            return Err(CircuitError::InvalidInput(format!(
+                "Leaf {} does not hold the requested leaf",
+                index
+            )));
+        }
+
         let mut path = Vec::with_capacity(self.depth);
         let mut indices = Vec::with_capacity(self.depth);
         
@@ -60,25 +225,130 @@ impl MerkleTree {
         for level in 0..self.depth {
             let is_right = current_index & 1 == 1;
             indices.push(is_right);
-            path.push(self.zero_values[level]);
+            path.push(self.node(level, current_index ^ 1));
             current_index >>= 1;
         }
         
-        (path, indices)
+        Ok((path, indices))
     }
 
     pub fn get_empty_root(&self) -> [u8; 32] {
         self.zero_values[self.depth]
     }
 
+    /// Checks `proof` for `leaf` against `proof.root` at this tree's depth.
     pub fn verify_proof(&self, proof: &MerkleProof, leaf: &[u8; 32]) -> bool {
-        let computed_root = self.compute_root_from_path(leaf, &proof.path, &proof.indices);
-        computed_root == proof.root
+        verify_merkle_proof_with::<H>(leaf, proof, &proof.root, self.depth)
+    }
+}
+
+impl PoseidonMerkleTree {
+    /// Builds a withdrawal witness whose path, path bits and leaf index are all
+    /// taken from this tree, after checking the leaf holds the commitment of
+    /// `secret`/`nullifier_seed`. Only the Poseidon tree has the layout the
+    /// circuit folds, so its root is the witness's `merkle_root`.
+    pub fn withdrawal_witness(
+        &self,
+        leaf_index: u32,
+        secret: [u8; 32],
+        nullifier_seed: [u8; 32],
+        amount: u64,
+    ) -> Result<WithdrawalWitness, CircuitError> {
+        let index = leaf_index as usize;
+        if index >= self.capacity() {
+            return Err(CircuitError::InvalidInput(format!(
+                "Leaf index {} outside tree capacity {}",
+                leaf_index,
+                self.capacity()
+            )));
+        }
+
+        let commitment = commitment_bytes(&secret, &nullifier_seed);
+        if self.leaf(index) != commitment {
+            return Err(CircuitError::InvalidInput(format!(
+                "Leaf {} does not hold the commitment for this secret",
+                leaf_index
+            )));
+        }
+
+        let (merkle_path, path_indices) = self.generate_proof_for_leaf(&commitment, index)?;
+
+        Ok(WithdrawalWitness {
+            secret,
+            nullifier_seed,
+            amount,
+            leaf_index,
+            merkle_path,
+            path_indices,
+        })
+    }
+}
+
+/// Checks `proof` for `leaf` against a root the caller already trusts, without
+/// needing the tree. `proof.root` is ignored. The path must be exactly `depth`
+/// long, so a node from a lower level cannot pass as a leaf on a shorter path.
+pub fn verify_merkle_proof(leaf: &[u8; 32], proof: &MerkleProof, expected_root: &[u8; 32], depth: usize) -> bool {
+    verify_merkle_proof_with::<Sha256Hasher>(leaf, proof, expected_root, depth)
+}
+
+/// `verify_merkle_proof` for a tree hashed with `H`.
+pub fn verify_merkle_proof_with<H: MerkleHasher>(
+    leaf: &[u8; 32],
+    proof: &MerkleProof,
+    expected_root: &[u8; 32],
+    depth: usize,
+) -> bool {
+    proof.path.len() == depth
+        && proof.indices.len() == depth
+        && root_from_path::<H>(leaf, &proof.path, &proof.indices) == *expected_root
+}
+
+fn root_from_path<H: MerkleHasher>(leaf: &[u8; 32], path: &[[u8; 32]], indices: &[bool]) -> [u8; 32] {
+    fold_merkle_path(H::leaf(leaf), path, indices, H::node)
+}
+
+/// Hashes `leaf` up through `path`. `indices[i]` is true when the running
+/// node is the right child at level `i`, so it goes second into `hasher`.
+/// Every path fold in the crate goes through here, whatever the hash.
+pub fn fold_merkle_path<T: Copy>(leaf: T, path: &[T], indices: &[bool], hasher: impl Fn(&T, &T) -> T) -> T {
+    path.iter()
+        .zip(indices)
+        .fold(leaf, |current, (sibling, &is_right)| {
+            if is_right {
+                hasher(sibling, &current)
+            } else {
+                hasher(&current, sibling)
+            }
+        })
+}
+
+/// Recombines little-endian path bits into a leaf index. Slices longer than
+/// the bit width of `u64` are rejected instead of overflowing the shift.
+pub fn path_indices_to_index(indices: &[bool]) -> Result<u64, CircuitError> {
+    if indices.len() > u64::BITS as usize {
+        return Err(CircuitError::InvalidInput(format!(
+            "Path of length {} does not fit in a 64-bit index",
+            indices.len()
+        )));
     }
+
+    Ok(indices
+        .iter()
+        .enumerate()
+        .filter(|(_, &bit)| bit)
+        .fold(0u64, |index, (i, _)| index | (1u64 << i)))
+}
+
+pub fn leaf_hash(leaf: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_DOMAIN]);
+    hasher.update(leaf);
+    hasher.finalize().into()
 }
 
 pub fn merkle_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
     let mut hasher = Sha256::new();
+    hasher.update([NODE_DOMAIN]);
     hasher.update(left);
     hasher.update(right);
     hasher.finalize().into()
@@ -97,10 +367,11 @@ mod tests {
 
     #[test]
     fn test_merkle_proof_generation() {
-        let tree = MerkleTree::new(MERKLE_TREE_DEPTH);
+        let mut tree = MerkleTree::new(MERKLE_TREE_DEPTH);
         let leaf = [42u8; 32];
+        tree.insert(0, leaf).unwrap();
         
-        let (path, indices) = tree.generate_proof_for_leaf(&leaf, 0);
+        let (path, indices) = tree.generate_proof_for_leaf(&leaf, 0).unwrap();
         
         assert_eq!(path.len(), MERKLE_TREE_DEPTH);
         assert_eq!(indices.len(), MERKLE_TREE_DEPTH);
@@ -108,10 +379,11 @@ mod tests {
 
     #[test]
     fn test_merkle_root_computation() {
-        let tree = MerkleTree::new(MERKLE_TREE_DEPTH);
+        let mut tree = MerkleTree::new(MERKLE_TREE_DEPTH);
         let leaf = [42u8; 32];
+        tree.insert(0, leaf).unwrap();
         
-        let (path, indices) = tree.generate_proof_for_leaf(&leaf, 0);
+        let (path, indices) = tree.generate_proof_for_leaf(&leaf, 0).unwrap();
         let root = tree.compute_root_from_path(&leaf, &path, &indices);
         
         assert_ne!(root, [0u8; 32]);
@@ -119,10 +391,11 @@ mod tests {
 
     #[test]
     fn test_merkle_proof_verification() {
-        let tree = MerkleTree::new(MERKLE_TREE_DEPTH);
+        let mut tree = MerkleTree::new(MERKLE_TREE_DEPTH);
         let leaf = [42u8; 32];
+        tree.insert(0, leaf).unwrap();
         
-        let (path, indices) = tree.generate_proof_for_leaf(&leaf, 0);
+        let (path, indices) = tree.generate_proof_for_leaf(&leaf, 0).unwrap();
         let root = tree.compute_root_from_path(&leaf, &path, &indices);
         
         let proof = MerkleProof { path, indices, root };
@@ -131,16 +404,298 @@ mod tests {
 
     #[test]
     fn test_different_leaves_different_roots() {
-        let tree = MerkleTree::new(MERKLE_TREE_DEPTH);
+        let mut tree1 = MerkleTree::new(MERKLE_TREE_DEPTH);
+        let mut tree2 = MerkleTree::new(MERKLE_TREE_DEPTH);
         let leaf1 = [1u8; 32];
         let leaf2 = [2u8; 32];
+        tree1.insert(0, leaf1).unwrap();
+        tree2.insert(0, leaf2).unwrap();
         
-        let (path1, indices1) = tree.generate_proof_for_leaf(&leaf1, 0);
-        let (path2, indices2) = tree.generate_proof_for_leaf(&leaf2, 0);
+        let (path1, indices1) = tree1.generate_proof_for_leaf(&leaf1, 0).unwrap();
+        let (path2, indices2) = tree2.generate_proof_for_leaf(&leaf2, 0).unwrap();
         
-        let root1 = tree.compute_root_from_path(&leaf1, &path1, &indices1);
-        let root2 = tree.compute_root_from_path(&leaf2, &path2, &indices2);
+        let root1 = tree1.compute_root_from_path(&leaf1, &path1, &indices1);
+        let root2 = tree2.compute_root_from_path(&leaf2, &path2, &indices2);
         
         assert_ne!(root1, root2);
     }
+
+    #[test]
+    fn test_path_indices_to_index() {
+        let mut tree = MerkleTree::new(MERKLE_TREE_DEPTH);
+        tree.insert(0b1011_0001, [9u8; 32]).unwrap();
+        let (_, indices) = tree.generate_proof_for_leaf(&[9u8; 32], 0b1011_0001).unwrap();
+        assert_eq!(indices.len(), 20);
+        assert_eq!(path_indices_to_index(&indices).unwrap(), 0b1011_0001);
+
+        let all_set = vec![true; 64];
+        assert_eq!(path_indices_to_index(&all_set).unwrap(), u64::MAX);
+
+        let too_long = vec![false; 65];
+        assert!(path_indices_to_index(&too_long).is_err());
+    }
+
+    #[test]
+    fn test_insert_updates_root_and_proof() {
+        let mut tree = MerkleTree::new(MERKLE_TREE_DEPTH);
+        let empty_root = tree.root();
+        let leaf = [7u8; 32];
+
+        let root = tree.insert(3, leaf).unwrap();
+        assert_ne!(root, empty_root);
+
+        let (path, indices) = tree.generate_proof_for_leaf(&leaf, 3).unwrap();
+        assert_eq!(tree.compute_root_from_path(&leaf, &path, &indices), root);
+    }
+
+    #[test]
+    fn test_proof_for_leaf_rejects_other_leaf() {
+        let mut tree = MerkleTree::new(MERKLE_TREE_DEPTH);
+        tree.insert_batch(0, &[[1u8; 32], [2u8; 32]]).unwrap();
+
+        assert!(tree.generate_proof_for_leaf(&[1u8; 32], 0).is_ok());
+        assert!(matches!(tree.generate_proof_for_leaf(&[2u8; 32], 0), Err(CircuitError::InvalidInput(_))));
+        assert!(tree.generate_proof_for_leaf(&[0u8; 32], 2).is_err());
+        assert!(tree.generate_proof_for_leaf(&[1u8; 32], tree.capacity()).is_err());
+    }
+
+    #[test]
+    fn test_fold_merkle_path_orders_children() {
+        // A non-commutative "hash" makes the child order visible.
+        let hasher = |left: &u64, right: &u64| left * 1000 + right;
+        assert_eq!(fold_merkle_path(1, &[2, 3], &[false, false], hasher), 1_002_003);
+        assert_eq!(fold_merkle_path(1, &[2, 3], &[true, false], hasher), 2_001_003);
+        assert_eq!(fold_merkle_path(1, &[2, 3], &[true, true], hasher), 3_002_001);
+        assert_eq!(fold_merkle_path(7, &[], &[], hasher), 7);
+    }
+
+    #[test]
+    fn test_fold_merkle_path_matches_both_trees() {
+        let mut tree = MerkleTree::new(MERKLE_TREE_DEPTH);
+        tree.insert_batch(4, &[[1u8; 32], [2u8; 32], [3u8; 32]]).unwrap();
+        let (path, indices) = tree.generate_proof_for_leaf(&[2u8; 32], 5).unwrap();
+        assert_eq!(fold_merkle_path(leaf_hash(&[2u8; 32]), &path, &indices, merkle_hash), tree.root());
+
+        use crate::withdrawal_circuit::{compute_commitment, MERKLE_DEPTH};
+
+        let witness = WithdrawalWitness {
+            secret: [5u8; 32],
+            nullifier_seed: [6u8; 32],
+            amount: 1,
+            leaf_index: 5,
+            merkle_path: (0..MERKLE_DEPTH as u8).map(|i| [i; 32]).collect(),
+            path_indices: (0..MERKLE_DEPTH).map(|level| (5 >> level) & 1 == 1).collect(),
+        };
+        let siblings: Vec<Fr> = witness.merkle_path.iter().map(field_from_bytes::<Fr>).collect();
+        let leaf = compute_commitment(field_from_bytes::<Fr>(&witness.secret), field_from_bytes::<Fr>(&witness.nullifier_seed));
+        let root = fold_merkle_path(leaf, &siblings, &witness.path_indices, |l, r| poseidon_hash_native(&[*l, *r]));
+        assert_eq!(witness.public_inputs::<Fr>([0xab; 20]).merkle_root, field_to_bytes(root));
+    }
+
+    #[test]
+    fn test_proof_for_leaf_roundtrip() {
+        let mut tree = MerkleTree::new(MERKLE_TREE_DEPTH);
+        let deposits: Vec<[u8; 32]> = (0..5).map(|_| crate::commitment::generate_deposit().commitment).collect();
+        tree.insert_batch(10, &deposits).unwrap();
+        let root = tree.root();
+
+        for (offset, leaf) in deposits.iter().enumerate() {
+            let (path, indices) = tree.generate_proof_for_leaf(leaf, 10 + offset).unwrap();
+            assert_eq!(path_indices_to_index(&indices).unwrap(), 10 + offset as u64);
+            assert_eq!(tree.compute_root_from_path(leaf, &path, &indices), root);
+            assert_ne!(tree.compute_root_from_path(&deposits[(offset + 1) % deposits.len()], &path, &indices), root);
+            assert!(tree.verify_proof(&MerkleProof { path, indices, root }, leaf));
+        }
+    }
+
+    #[test]
+    fn test_insert_batch_matches_sequential_inserts() {
+        let leaves: Vec<[u8; 32]> = (0..1024u32)
+            .map(|i| {
+                let mut leaf = [0u8; 32];
+                leaf[..4].copy_from_slice(&i.to_le_bytes());
+                leaf
+            })
+            .collect();
+
+        let mut sequential = MerkleTree::new(MERKLE_TREE_DEPTH);
+        for (i, leaf) in leaves.iter().enumerate() {
+            sequential.insert(i, *leaf).unwrap();
+        }
+
+        let mut batched = MerkleTree::new(MERKLE_TREE_DEPTH);
+        let root = batched.insert_batch(0, &leaves).unwrap();
+
+        assert_eq!(root, sequential.root());
+    }
+
+    #[test]
+    fn test_withdrawal_witness_from_tree() {
+        use halo2_proofs::dev::MockProver;
+        use crate::withdrawal_circuit::{WithdrawalCircuit, MERKLE_DEPTH};
+
+        let mut tree = PoseidonMerkleTree::empty(MERKLE_DEPTH);
+        let deposit = crate::commitment::generate_deposit();
+        let others: Vec<[u8; 32]> = (0..5).map(|_| crate::commitment::generate_deposit().commitment).collect();
+        tree.insert_batch(0, &others).unwrap();
+        tree.insert(5, deposit.commitment).unwrap();
+
+        let witness = tree
+            .withdrawal_witness(5, deposit.secret, deposit.nullifier_seed, 100)
+            .unwrap();
+
+        assert_eq!(path_indices_to_index(&witness.path_indices).unwrap(), witness.leaf_index as u64);
+        assert_eq!(
+            tree.compute_root_from_path(&deposit.commitment, &witness.merkle_path, &witness.path_indices),
+            tree.root()
+        );
+
+        let public_inputs = witness.public_inputs::<Fr>([0xab; 20]);
+        assert_eq!(public_inputs.merkle_root, tree.root());
+
+        let instances = public_inputs.instances::<Fr>().unwrap();
+        let circuit = WithdrawalCircuit::<Fr>::new(witness, public_inputs);
+        let prover = MockProver::run(11, &circuit, vec![instances]).unwrap();
+        prover.verify().unwrap();
+
+        assert!(tree.withdrawal_witness(4, deposit.secret, deposit.nullifier_seed, 100).is_err());
+    }
+
+    #[test]
+    fn test_poseidon_tree_empty_subtrees_match_circuit() {
+        use crate::withdrawal_circuit::{zero_hashes, MERKLE_DEPTH};
+
+        let tree = PoseidonMerkleTree::empty(MERKLE_DEPTH);
+        let zeros: Vec<[u8; 32]> = zero_hashes::<Fr>(MERKLE_DEPTH).into_iter().map(field_to_bytes).collect();
+        assert_eq!(tree.zero_values, zeros);
+        assert_eq!(tree.root(), zeros[MERKLE_DEPTH]);
+    }
+
+    #[test]
+    fn test_internal_node_does_not_verify_as_leaf() {
+        let mut tree = MerkleTree::new(MERKLE_TREE_DEPTH);
+        tree.insert_batch(0, &[[1u8; 32], [2u8; 32], [3u8; 32], [4u8; 32]]).unwrap();
+        let root = tree.root();
+
+        let (path, indices) = tree.generate_proof_for_leaf(&[1u8; 32], 0).unwrap();
+        assert!(tree.verify_proof(&MerkleProof { path: path.clone(), indices: indices.clone(), root }, &[1u8; 32]));
+
+        // Present the level-1 node over leaves 0 and 1 as if it were a leaf
+        // with the remaining, shorter path.
+        let internal = tree.node(1, 0);
+        assert_ne!(tree.compute_root_from_path(&internal, &path[1..], &indices[1..]), root);
+
+        let short = MerkleProof { path: path[1..].to_vec(), indices: indices[1..].to_vec(), root };
+        assert!(!tree.verify_proof(&short, &internal));
+    }
+
+    #[test]
+    fn test_internal_node_with_full_length_path_does_not_verify() {
+        let mut tree = MerkleTree::new(MERKLE_TREE_DEPTH);
+        tree.insert_batch(0, &[[1u8; 32], [2u8; 32], [3u8; 32], [4u8; 32]]).unwrap();
+        let root = tree.root();
+        let (path, indices) = tree.generate_proof_for_leaf(&[1u8; 32], 0).unwrap();
+        let internal = tree.node(1, 0);
+
+        // Folded without the leaf prefix, the internal node reaches the root;
+        // the leaf domain is what keeps it from being read as a leaf.
+        assert_eq!(fold_merkle_path(internal, &path[1..], &indices[1..], merkle_hash), root);
+
+        // Full-length paths for the internal node: the real path of leaf 0,
+        // and its upper levels padded back to the full depth.
+        let mut padded_path = path[1..].to_vec();
+        padded_path.push(tree.zero_values[MERKLE_TREE_DEPTH - 1]);
+        let mut padded_indices = indices[1..].to_vec();
+        padded_indices.push(false);
+
+        for (path, indices) in [(path, indices), (padded_path, padded_indices)] {
+            assert_eq!(path.len(), MERKLE_TREE_DEPTH);
+            let proof = MerkleProof { path, indices, root };
+            assert!(!tree.verify_proof(&proof, &internal));
+            assert!(!verify_merkle_proof(&internal, &proof, &root, MERKLE_TREE_DEPTH));
+        }
+    }
+
+    #[test]
+    fn test_verify_against_trusted_root_without_tree() {
+        let mut tree = MerkleTree::new(MERKLE_TREE_DEPTH);
+        tree.insert_batch(0, &[[5u8; 32], [6u8; 32], [7u8; 32]]).unwrap();
+        let (path, indices) = tree.generate_proof_for_leaf(&[6u8; 32], 1).unwrap();
+        let trusted_root = tree.root();
+        drop(tree);
+
+        let proof = MerkleProof { path, indices, root: [0u8; 32] };
+        assert!(verify_merkle_proof(&[6u8; 32], &proof, &trusted_root, MERKLE_TREE_DEPTH));
+        assert!(!verify_merkle_proof(&[7u8; 32], &proof, &trusted_root, MERKLE_TREE_DEPTH));
+        assert!(!verify_merkle_proof(&[6u8; 32], &proof, &trusted_root, MERKLE_TREE_DEPTH - 1));
+
+        let mut truncated = proof.clone();
+        truncated.indices.pop();
+        assert!(!verify_merkle_proof(&[6u8; 32], &truncated, &trusted_root, MERKLE_TREE_DEPTH));
+
+        // Dropping the top level from both keeps them the same length, but
+        // the path no longer matches the depth.
+        truncated.path.pop();
+        let level_below_root = root_from_path::<Sha256Hasher>(&[6u8; 32], &truncated.path, &truncated.indices);
+        assert!(!verify_merkle_proof(&[6u8; 32], &truncated, &level_below_root, MERKLE_TREE_DEPTH));
+        assert!(verify_merkle_proof(&[6u8; 32], &truncated, &level_below_root, MERKLE_TREE_DEPTH - 1));
+    }
+
+    #[test]
+    fn test_insert_batch_rejects_overflow() {
+        let mut tree = MerkleTree::new(2);
+        assert!(tree.insert_batch(3, &[[1u8; 32], [2u8; 32]]).is_err());
+        assert!(tree.insert(4, [1u8; 32]).is_err());
+    }
+
+    /// Root of a tree built by hashing every leaf slot, empty ones included.
+    fn dense_root(depth: usize, leaves: &[(usize, [u8; 32])]) -> [u8; 32] {
+        let mut level: Vec<[u8; 32]> = vec![[0u8; 32]; 1 << depth];
+        for (index, leaf) in leaves {
+            level[*index] = leaf_hash(leaf);
+        }
+        while level.len() > 1 {
+            level = level.chunks(2).map(|pair| merkle_hash(&pair[0], &pair[1])).collect();
+        }
+        level[0]
+    }
+
+    #[test]
+    fn test_sparse_tree_matches_dense_tree() {
+        let depth = 10;
+        let leaves = [(0, [1u8; 32]), (517, [2u8; 32]), (1023, [3u8; 32])];
+
+        let mut tree = MerkleTree::new(depth);
+        assert_eq!(tree.root(), dense_root(depth, &[]));
+        for (index, leaf) in leaves {
+            tree.insert(index, leaf).unwrap();
+        }
+
+        assert_eq!(tree.root(), dense_root(depth, &leaves));
+        // Only the inserted leaves and their ancestors are stored.
+        assert!(tree.nodes.len() <= leaves.len() * (depth + 1));
+        for (index, leaf) in leaves {
+            let (path, indices) = tree.generate_proof_for_leaf(&leaf, index).unwrap();
+            assert_eq!(tree.compute_root_from_path(&leaf, &path, &indices), tree.root());
+        }
+    }
+
+    #[test]
+    fn test_duplicate_commitment_is_rejected() {
+        let mut tree = MerkleTree::new(MERKLE_TREE_DEPTH);
+        let deposit = crate::commitment::generate_deposit();
+        let root = tree.insert_batch(0, &[[1u8; 32], deposit.commitment]).unwrap();
+
+        assert!(matches!(tree.insert(2, deposit.commitment), Err(CircuitError::InvalidInput(_))));
+        assert!(tree.insert_batch(2, &[[3u8; 32], [3u8; 32]]).is_err());
+        assert!(tree.insert_batch(2, &[[4u8; 32], [1u8; 32]]).is_err());
+        assert_eq!(tree.root(), root);
+        assert_eq!(tree.leaf(2), [0u8; 32]);
+
+        // Rewriting a leaf in place is not a second deposit, and a replaced
+        // commitment may be inserted again elsewhere.
+        assert_eq!(tree.insert(1, deposit.commitment).unwrap(), root);
+        tree.insert(0, [5u8; 32]).unwrap();
+        tree.insert(2, [1u8; 32]).unwrap();
+    }
 }
@@ -0,0 +1,184 @@
+use std::marker::PhantomData;
+use ff::PrimeField;
+use halo2_proofs::{
+    circuit::{AssignedCell, Chip, Layouter, Value},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+
+use crate::poseidon::PoseidonChip;
+
+#[derive(Clone, Debug)]
+pub struct MerklePathConfig {
+    pub advice: [Column<Advice>; 5],
+    pub s_merkle: Selector,
+    pub s_index: Selector,
+}
+
+/// Folds a leaf up a Poseidon merkle path whose direction bits are the binary
+/// digits of the leaf index, so a proof can't use one index for its
+/// nullifier and another position's path.
+pub struct MerklePathChip<F: PrimeField> {
+    config: MerklePathConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> Chip<F> for MerklePathChip<F> {
+    type Config = MerklePathConfig;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl<F: PrimeField> MerklePathChip<F> {
+    pub fn construct(config: MerklePathConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The index remainder after the last level is pinned with a constant, so
+    /// the circuit must have called `enable_constant` on some fixed column.
+    pub fn configure(meta: &mut ConstraintSystem<F>, advice: [Column<Advice>; 5]) -> MerklePathConfig {
+        let s_merkle = meta.selector();
+        let s_index = meta.selector();
+        for col in advice.iter() {
+            meta.enable_equality(*col);
+        }
+
+        // Orders (current, sibling) into the (left, right) pair that the
+        // Poseidon chip hashes into the parent. is_right is shown boolean by
+        // the path_index_bits gate on the cell it is copied from.
+        meta.create_gate("merkle_step", |meta| {
+            let s = meta.query_selector(s_merkle);
+            let current = meta.query_advice(advice[0], Rotation::cur());
+            let sibling = meta.query_advice(advice[1], Rotation::cur());
+            let is_right = meta.query_advice(advice[2], Rotation::cur());
+            let left = meta.query_advice(advice[3], Rotation::cur());
+            let right = meta.query_advice(advice[4], Rotation::cur());
+
+            vec![
+                s.clone() * (left - current.clone() - is_right.clone() * (sibling.clone() - current.clone())),
+                s * (right - sibling.clone() - is_right * (current - sibling)),
+            ]
+        });
+
+        // advice[4] on the path index rows carries leaf_index >> level. Each
+        // step peels off the low bit, which must be that level's is_right
+        // flag, and the remainder after the last level is pinned to zero.
+        meta.create_gate("path_index_bits", |meta| {
+            let s = meta.query_selector(s_index);
+            let is_right = meta.query_advice(advice[2], Rotation::cur());
+            let remainder = meta.query_advice(advice[4], Rotation::cur());
+            let next_remainder = meta.query_advice(advice[4], Rotation::next());
+
+            let one = Expression::Constant(F::ONE);
+            let two = Expression::Constant(F::from(2u64));
+
+            vec![
+                s.clone() * is_right.clone() * (one - is_right.clone()),
+                s * (remainder - next_remainder * two - is_right),
+            ]
+        });
+
+        MerklePathConfig {
+            advice,
+            s_merkle,
+            s_index,
+        }
+    }
+
+    /// Splits `leaf_index` into one boolean cell per entry of `indices`,
+    /// least significant first. An index with bits above `indices.len()`
+    /// leaves a nonzero final remainder and fails.
+    pub fn path_bits(
+        &self,
+        mut layouter: impl Layouter<F>,
+        leaf_index: &AssignedCell<F, F>,
+        indices: &[Value<bool>],
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        let config = &self.config;
+        let bits: Vec<Value<F>> = indices.iter().map(|bit| bit.map(|bit| F::from(bit as u64))).collect();
+
+        layouter.assign_region(
+            || "path_index_bits",
+            |mut region| {
+                leaf_index.copy_advice(|| "index_remainder_0", &mut region, config.advice[4], 0)?;
+
+                let mut cells = Vec::with_capacity(bits.len());
+                for (level, bit) in bits.iter().enumerate() {
+                    config.s_index.enable(&mut region, level)?;
+
+                    if level > 0 {
+                        let remainder = bits[level..]
+                            .iter()
+                            .rev()
+                            .fold(Value::known(F::ZERO), |acc, bit| acc.zip(*bit).map(|(acc, bit)| acc.double() + bit));
+                        region.assign_advice(
+                            || format!("index_remainder_{}", level),
+                            config.advice[4],
+                            level,
+                            || remainder,
+                        )?;
+                    }
+
+                    cells.push(region.assign_advice(|| format!("is_right_{}", level), config.advice[2], level, || *bit)?);
+                }
+
+                region.assign_advice_from_constant(
+                    || "index_remainder_final",
+                    config.advice[4],
+                    bits.len(),
+                    F::ZERO,
+                )?;
+                Ok(cells)
+            },
+        )
+    }
+
+    /// Hashes `leaf` up through `siblings`, ordering each pair by the matching
+    /// cell from `path_bits`, and returns the root cell.
+    pub fn root(
+        &self,
+        mut layouter: impl Layouter<F>,
+        poseidon: &PoseidonChip<F>,
+        leaf: AssignedCell<F, F>,
+        bits: &[AssignedCell<F, F>],
+        siblings: &[Value<F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let config = &self.config;
+        let mut current_hash = leaf;
+
+        for (level, (is_right, sibling)) in bits.iter().zip(siblings).enumerate() {
+            let (left, right) = layouter.assign_region(
+                || format!("merkle_step_{}", level),
+                |mut region| {
+                    config.s_merkle.enable(&mut region, 0)?;
+
+                    let current = current_hash.copy_advice(|| "current", &mut region, config.advice[0], 0)?;
+                    let is_right = is_right.copy_advice(|| "is_right", &mut region, config.advice[2], 0)?;
+                    let sibling = region.assign_advice(|| "sibling", config.advice[1], 0, || *sibling)?;
+
+                    let (left, right) = current.value().zip(sibling.value()).zip(is_right.value())
+                        .map(|((curr, sib), right)| if *right == F::ONE { (*sib, *curr) } else { (*curr, *sib) })
+                        .unzip();
+
+                    let left = region.assign_advice(|| "left", config.advice[3], 0, || left)?;
+                    let right = region.assign_advice(|| "right", config.advice[4], 0, || right)?;
+                    Ok((left, right))
+                },
+            )?;
+
+            current_hash = poseidon.hash(layouter.namespace(|| format!("merkle_hash_{}", level)), &[left, right])?;
+        }
+
+        Ok(current_hash)
+    }
+}
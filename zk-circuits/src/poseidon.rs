@@ -1,4 +1,7 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::sync::{OnceLock, RwLock};
 use ff::PrimeField;
 use halo2_proofs::{
     circuit::{AssignedCell, Chip, Layouter, Region, Value},
@@ -11,15 +14,67 @@ pub const POSEIDON_RATE: usize = 2;
 pub const POSEIDON_ROUNDS_F: usize = 8;
 pub const POSEIDON_ROUNDS_P: usize = 57;
 
-pub const MDS_MATRIX: [[u64; POSEIDON_WIDTH]; POSEIDON_WIDTH] = [
-    [2, 1, 1],
-    [1, 2, 1],
-    [1, 1, 2],
-];
+/// Lane that starts every permutation at zero (or, for the sponge, at the
+/// input length) and that the output is read from. Inputs go in the lanes
+/// after it, as in circomlib.
+const CAPACITY_LANE: usize = 0;
 
-fn get_round_constant(round: usize, index: usize) -> u64 {
-    let seed = (round * POSEIDON_WIDTH + index + 1) as u64;
-    seed.wrapping_mul(0x517cc1b727220a95).wrapping_add(0x96696969)
+mod constants;
+pub use constants::{MDS_MATRIX, ROUND_CONSTANTS};
+
+type ConstantCache = RwLock<HashMap<TypeId, &'static (dyn Any + Send + Sync)>>;
+
+struct PoseidonConstants<F> {
+    round_constants: Vec<[F; POSEIDON_WIDTH]>,
+    mds: [[F; POSEIDON_WIDTH]; POSEIDON_WIDTH],
+}
+
+/// `ROUND_CONSTANTS` and `MDS_MATRIX` parsed into `F` on first use and shared
+/// afterwards. A static can't be generic, so the cache is keyed by field type.
+fn constants<F: PrimeField>() -> &'static PoseidonConstants<F> {
+    static CACHE: OnceLock<ConstantCache> = OnceLock::new();
+    let cache = CACHE.get_or_init(Default::default);
+
+    let cached = cache.read().expect("poseidon constant cache poisoned").get(&TypeId::of::<F>()).copied();
+    let entry = match cached {
+        Some(entry) => entry,
+        None => *cache
+            .write()
+            .expect("poseidon constant cache poisoned")
+            .entry(TypeId::of::<F>())
+            .or_insert_with(|| {
+                let parsed: &'static PoseidonConstants<F> = Box::leak(Box::new(PoseidonConstants {
+                    round_constants: ROUND_CONSTANTS.iter().map(|round| round.map(field_from_hex)).collect(),
+                    mds: MDS_MATRIX.map(|row| row.map(field_from_hex)),
+                }));
+                parsed as &'static (dyn Any + Send + Sync)
+            }),
+    };
+    entry.downcast_ref().expect("cache entries are keyed by their field type")
+}
+
+/// `ROUND_CONSTANTS` as field elements, one row per round.
+pub fn round_constants<F: PrimeField>() -> &'static [[F; POSEIDON_WIDTH]] {
+    &constants::<F>().round_constants
+}
+
+/// `MDS_MATRIX` as field elements; `new[i] = sum_j mds[i][j] * state[j]`.
+pub fn mds_matrix<F: PrimeField>() -> &'static [[F; POSEIDON_WIDTH]; POSEIDON_WIDTH] {
+    &constants::<F>().mds
+}
+
+/// Parses a big-endian hex constant 64 bits at a time.
+fn field_from_hex<F: PrimeField>(hex: &str) -> F {
+    let limb_base = F::from(u64::MAX) + F::ONE;
+    hex.trim_start_matches("0x")
+        .as_bytes()
+        .rchunks(16)
+        .rev()
+        .map(|chunk| {
+            let chunk = std::str::from_utf8(chunk).expect("hex constants are ASCII");
+            u64::from_str_radix(chunk, 16).expect("invalid hex constant")
+        })
+        .fold(F::ZERO, |acc, limb| acc * limb_base + F::from(limb))
 }
 
 #[derive(Clone, Debug)]
@@ -28,6 +83,7 @@ pub struct PoseidonConfig {
     pub round_constants: [Column<Fixed>; POSEIDON_WIDTH],
     pub selector_full: Selector,
     pub selector_partial: Selector,
+    pub selector_absorb: Selector,
 }
 
 pub struct PoseidonChip<F: PrimeField> {
@@ -63,11 +119,14 @@ impl<F: PrimeField> PoseidonChip<F> {
     ) -> PoseidonConfig {
         let selector_full = meta.selector();
         let selector_partial = meta.selector();
+        let selector_absorb = meta.selector();
 
         for col in state.iter() {
             meta.enable_equality(*col);
         }
 
+        let mds = mds_matrix::<F>();
+
         meta.create_gate("poseidon_full_round", |meta| {
             let s = meta.query_selector(selector_full);
             let state_cur: Vec<_> = state.iter()
@@ -89,7 +148,7 @@ impl<F: PrimeField> PoseidonChip<F> {
                 
                 let mut mix = Expression::Constant(F::ZERO);
                 for j in 0..POSEIDON_WIDTH {
-                    let mds_entry = Expression::Constant(F::from(MDS_MATRIX[i][j]));
+                    let mds_entry = Expression::Constant(mds[i][j]);
                     let input_j = state_cur[j].clone() + rc[j].clone();
                     let sq_j = input_j.clone() * input_j.clone();
                     let quad_j = sq_j.clone() * sq_j.clone();
@@ -122,11 +181,11 @@ impl<F: PrimeField> PoseidonChip<F> {
             let sbox0_output = quad0 * sbox0_input;
             
             for i in 0..POSEIDON_WIDTH {
-                let mds_entry_0 = Expression::Constant(F::from(MDS_MATRIX[i][0]));
+                let mds_entry_0 = Expression::Constant(mds[i][0]);
                 let mut mix = mds_entry_0 * sbox0_output.clone();
                 
                 for j in 1..POSEIDON_WIDTH {
-                    let mds_entry = Expression::Constant(F::from(MDS_MATRIX[i][j]));
+                    let mds_entry = Expression::Constant(mds[i][j]);
                     mix = mix + mds_entry * (state_cur[j].clone() + rc[j].clone());
                 }
                 
@@ -135,14 +194,38 @@ impl<F: PrimeField> PoseidonChip<F> {
             constraints
         });
 
+        // Sponge block layout: the previous state on this row, the absorbed
+        // block in the rate lanes of the next, and the permutation's starting
+        // state on the row after that.
+        meta.create_gate("poseidon_absorb", |meta| {
+            let s = meta.query_selector(selector_absorb);
+            (0..POSEIDON_WIDTH)
+                .map(|i| {
+                    let prev = meta.query_advice(state[i], Rotation::cur());
+                    let next = meta.query_advice(state[i], Rotation(2));
+                    if i == CAPACITY_LANE {
+                        s.clone() * (next - prev)
+                    } else {
+                        let absorbed = meta.query_advice(state[i], Rotation::next());
+                        s.clone() * (next - prev - absorbed)
+                    }
+                })
+                .collect::<Vec<_>>()
+        });
+
         PoseidonConfig {
             state,
             round_constants,
             selector_full,
             selector_partial,
+            selector_absorb,
         }
     }
 
+    /// Hashes up to `POSEIDON_RATE` cells, laid out like
+    /// `poseidon_hash_native`. The inputs are copy-constrained into the
+    /// permutation and the other lanes are pinned to zero, so the circuit
+    /// must have called `enable_constant` on some fixed column.
     pub fn hash(
         &self,
         mut layouter: impl Layouter<F>,
@@ -156,6 +239,83 @@ impl<F: PrimeField> PoseidonChip<F> {
         )
     }
 
+    /// Variable-length sponge hash; see `poseidon_sponge_native` for the
+    /// padding rule. Each block is absorbed and permuted in its own region.
+    /// The inputs and padding are copy-constrained into the absorbed rows, the
+    /// length is pinned in the capacity lane, and each block starts from the
+    /// previous block's output cells, so the result is fixed by `inputs`.
+    pub fn hash_sponge(
+        &self,
+        layouter: impl Layouter<F>,
+        inputs: &[AssignedCell<F, F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let values = inputs.iter().map(|cell| cell.value().copied()).collect();
+        self.sponge(layouter, inputs, values)
+    }
+
+    /// `hash_sponge` absorbing `values` in place of the inputs' own values.
+    /// Each is still constrained equal to its input cell, which tests rely
+    /// on to play a prover that lies about what it absorbed.
+    fn sponge(
+        &self,
+        mut layouter: impl Layouter<F>,
+        inputs: &[AssignedCell<F, F>],
+        values: Vec<Value<F>>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let absorbed: Vec<Absorbed<'_, F>> = inputs.iter().zip(values).map(|(cell, value)| Absorbed::Input(cell, value)).collect();
+        let padded = sponge_pad(&absorbed, Absorbed::Padding(F::ZERO), Absorbed::Padding(F::ONE));
+
+        let mut state: Option<Vec<AssignedCell<F, F>>> = None;
+        for (block, chunk) in padded.chunks(POSEIDON_RATE).enumerate() {
+            let (_, output) = layouter.assign_region(
+                || format!("poseidon sponge block {}", block),
+                |mut region| {
+                    self.config.selector_absorb.enable(&mut region, 0)?;
+
+                    let prev: Vec<AssignedCell<F, F>> = match &state {
+                        Some(cells) => cells
+                            .iter()
+                            .zip(self.config.state.iter())
+                            .enumerate()
+                            .map(|(i, (cell, col))| cell.copy_advice(|| format!("prev_{}", i), &mut region, *col, 0))
+                            .collect::<Result<_, Error>>()?,
+                        None => self.config.state
+                            .iter()
+                            .enumerate()
+                            .map(|(i, col)| {
+                                let initial = if i == CAPACITY_LANE { F::from(inputs.len() as u64) } else { F::ZERO };
+                                region.assign_advice_from_constant(|| format!("prev_{}", i), *col, 0, initial)
+                            })
+                            .collect::<Result<_, Error>>()?,
+                    };
+
+                    let mut next: Vec<Value<F>> = prev.iter().map(|cell| cell.value().copied()).collect();
+                    for (j, lane) in chunk.iter().enumerate() {
+                        let lane_index = CAPACITY_LANE + 1 + j;
+                        let col = self.config.state[lane_index];
+                        let cell = match lane {
+                            Absorbed::Input(input, value) => {
+                                let cell = region.assign_advice(|| format!("absorb_{}", j), col, 1, || *value)?;
+                                region.constrain_equal(input.cell(), cell.cell())?;
+                                cell
+                            }
+                            Absorbed::Padding(value) => {
+                                region.assign_advice_from_constant(|| format!("pad_{}", j), col, 1, *value)?
+                            }
+                        };
+                        next[lane_index] = next[lane_index] + cell.value().copied();
+                    }
+
+                    self.permute(&mut region, 2, next)
+                },
+            )?;
+            state = Some(output);
+        }
+
+        let mut output = state.expect("padding always yields at least one block");
+        Ok(output.swap_remove(CAPACITY_LANE))
+    }
+
     fn hash_inner(
         &self,
         region: &mut Region<'_, F>,
@@ -165,58 +325,94 @@ impl<F: PrimeField> PoseidonChip<F> {
         
         for (i, input) in inputs.iter().enumerate() {
             if i < POSEIDON_RATE {
-                state[i] = input.value().copied();
+                state[CAPACITY_LANE + 1 + i] = input.value().copied();
             }
         }
 
+        let (initial, mut output) = self.permute(region, 0, state)?;
+        let rate_lanes = &initial[CAPACITY_LANE + 1..];
+        for (input, lane) in inputs.iter().zip(rate_lanes.iter()) {
+            region.constrain_equal(input.cell(), lane.cell())?;
+        }
+        region.constrain_constant(initial[CAPACITY_LANE].cell(), F::ZERO)?;
+        for lane in rate_lanes.iter().skip(inputs.len()) {
+            region.constrain_constant(lane.cell(), F::ZERO)?;
+        }
+        Ok(output.swap_remove(CAPACITY_LANE))
+    }
+
+    /// Lays out the full permutation starting at row `offset` of `region` and
+    /// returns the cells of the initial and the final state.
+    #[allow(clippy::type_complexity)]
+    fn permute(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        mut state: Vec<Value<F>>,
+    ) -> Result<(Vec<AssignedCell<F, F>>, Vec<AssignedCell<F, F>>), Error> {
         let total_rounds = POSEIDON_ROUNDS_F + POSEIDON_ROUNDS_P;
         let half_full = POSEIDON_ROUNDS_F / 2;
+        let rc = round_constants::<F>();
+        let mds = mds_matrix::<F>();
+        let mut initial = Vec::with_capacity(POSEIDON_WIDTH);
 
         for round in 0..total_rounds {
             let is_full_round = round < half_full || round >= half_full + POSEIDON_ROUNDS_P;
             
             if is_full_round {
-                self.config.selector_full.enable(region, round)?;
+                self.config.selector_full.enable(region, offset + round)?;
             } else {
-                self.config.selector_partial.enable(region, round)?;
+                self.config.selector_partial.enable(region, offset + round)?;
             }
 
             for (i, col) in self.config.round_constants.iter().enumerate() {
                 region.assign_fixed(
                     || format!("rc_{}_{}", round, i),
                     *col,
-                    round,
-                    || Value::known(F::from(get_round_constant(round, i))),
+                    offset + round,
+                    || Value::known(rc[round][i]),
                 )?;
             }
 
             for (i, col) in self.config.state.iter().enumerate() {
-                region.assign_advice(
+                let cell = region.assign_advice(
                     || format!("state_{}_{}", round, i),
                     *col,
-                    round,
+                    offset + round,
                     || state[i],
                 )?;
+                if round == 0 {
+                    initial.push(cell);
+                }
             }
 
-            state = self.permute_round(&state, round, is_full_round);
+            state = self.permute_round(&state, &rc[round], mds, is_full_round);
         }
 
-        let output = region.assign_advice(
-            || "output",
-            self.config.state[0],
-            total_rounds,
-            || state[0],
-        )?;
-
-        Ok(output)
+        // The last round's gate reads the whole next row, so every lane of the
+        // final state is assigned even though only one lane is the output.
+        let output = self.config.state
+            .iter()
+            .enumerate()
+            .map(|(i, col)| {
+                region.assign_advice(
+                    || format!("output_{}", i),
+                    *col,
+                    offset + total_rounds,
+                    || state[i],
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((initial, output))
     }
 
-    fn permute_round(&self, state: &[Value<F>], round: usize, is_full: bool) -> Vec<Value<F>> {
-        let rc: Vec<F> = (0..POSEIDON_WIDTH)
-            .map(|i| F::from(get_round_constant(round, i)))
-            .collect();
-
+    fn permute_round(
+        &self,
+        state: &[Value<F>],
+        rc: &[F; POSEIDON_WIDTH],
+        mds: &[[F; POSEIDON_WIDTH]; POSEIDON_WIDTH],
+        is_full: bool,
+    ) -> Vec<Value<F>> {
         let sboxed: Vec<Value<F>> = if is_full {
             state.iter().zip(rc.iter())
                 .map(|(s, r)| {
@@ -245,31 +441,79 @@ impl<F: PrimeField> PoseidonChip<F> {
         let mut mixed = vec![Value::known(F::ZERO); POSEIDON_WIDTH];
         for i in 0..POSEIDON_WIDTH {
             for j in 0..POSEIDON_WIDTH {
-                let mds = F::from(MDS_MATRIX[i][j]);
-                mixed[i] = mixed[i] + sboxed[j].map(|v| v * mds);
+                let entry = mds[i][j];
+                mixed[i] = mixed[i] + sboxed[j].map(|v| v * entry);
             }
         }
         mixed
     }
 }
 
+/// Poseidon over up to `POSEIDON_RATE` elements with the circomlib layout:
+/// the state starts as `[0, inputs..]` (zero-filled), is permuted once, and
+/// lane 0 is the hash. `poseidon_hash_native(&[a, b])` equals circomlib's
+/// `poseidon([a, b])`.
 pub fn poseidon_hash_native<F: PrimeField>(inputs: &[F]) -> F {
     let mut state = [F::ZERO; POSEIDON_WIDTH];
     
     for (i, input) in inputs.iter().enumerate() {
         if i < POSEIDON_RATE {
-            state[i] = *input;
+            state[CAPACITY_LANE + 1 + i] = *input;
         }
     }
 
-    let total_rounds = POSEIDON_ROUNDS_F + POSEIDON_ROUNDS_P;
+    poseidon_permute_native(&mut state);
+    state[CAPACITY_LANE]
+}
+
+/// Hashes any number of field elements.
+///
+/// Padding is `10*`: a single one is appended after the inputs, then zeros up
+/// to a multiple of `POSEIDON_RATE`. The input length is additionally written
+/// into the capacity lane before the first permutation, so inputs that only
+/// differ by trailing zeros (`[a]`, `[a, 0]`, `[a, 0, 0]`) never share a
+/// padded stream or a starting state. Blocks are added into the rate lanes and
+/// the state is permuted after each; the output is the capacity lane.
+pub fn poseidon_sponge_native<F: PrimeField>(inputs: &[F]) -> F {
+    let mut state = [F::ZERO; POSEIDON_WIDTH];
+    state[CAPACITY_LANE] = F::from(inputs.len() as u64);
+
+    for chunk in sponge_pad(inputs, F::ZERO, F::ONE).chunks(POSEIDON_RATE) {
+        for (lane, value) in chunk.iter().enumerate() {
+            state[CAPACITY_LANE + 1 + lane] += *value;
+        }
+        poseidon_permute_native(&mut state);
+    }
+
+    state[CAPACITY_LANE]
+}
+
+/// One rate lane of a sponge block: an input cell with the value absorbed
+/// for it, or a padding constant.
+#[derive(Clone)]
+enum Absorbed<'a, F: PrimeField> {
+    Input(&'a AssignedCell<F, F>, Value<F>),
+    Padding(F),
+}
+
+fn sponge_pad<T: Clone>(inputs: &[T], zero: T, one: T) -> Vec<T> {
+    let mut padded = inputs.to_vec();
+    padded.push(one);
+    while padded.len() % POSEIDON_RATE != 0 {
+        padded.push(zero.clone());
+    }
+    padded
+}
+
+fn poseidon_permute_native<F: PrimeField>(state: &mut [F; POSEIDON_WIDTH]) {
     let half_full = POSEIDON_ROUNDS_F / 2;
+    let mds = mds_matrix::<F>();
 
-    for round in 0..total_rounds {
+    for (round, rc) in round_constants::<F>().iter().enumerate() {
         let is_full = round < half_full || round >= half_full + POSEIDON_ROUNDS_P;
         
         for i in 0..POSEIDON_WIDTH {
-            state[i] += F::from(get_round_constant(round, i));
+            state[i] += rc[i];
         }
 
         if is_full {
@@ -287,20 +531,22 @@ pub fn poseidon_hash_native<F: PrimeField>(inputs: &[F]) -> F {
         let mut new_state = [F::ZERO; POSEIDON_WIDTH];
         for i in 0..POSEIDON_WIDTH {
             for j in 0..POSEIDON_WIDTH {
-                new_state[i] += F::from(MDS_MATRIX[i][j]) * state[j];
+                new_state[i] += mds[i][j] * state[j];
             }
         }
-        state = new_state;
+        *state = new_state;
     }
-
-    state[0]
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use ff::Field;
-    use halo2_proofs::halo2curves::bn256::Fr;
+    use halo2_proofs::{
+        circuit::SimpleFloorPlanner,
+        halo2curves::bn256::Fr,
+        plonk::{Circuit, Instance},
+    };
 
     #[test]
     fn test_poseidon_native() {
@@ -315,4 +561,228 @@ mod tests {
         let hash3 = poseidon_hash_native(&inputs2);
         assert_ne!(hash, hash3);
     }
+
+    #[test]
+    fn test_round_constants_match_reference() {
+        let rc = round_constants::<Fr>();
+        assert_eq!(rc.len(), POSEIDON_ROUNDS_F + POSEIDON_ROUNDS_P);
+
+        // First and last constants of the circomlib / poseidon-rs t = 3 set.
+        let first = Fr::from_str_vartime(
+            "6745197990210204598374042828761989596302876299545964402857411729872131034734",
+        ).unwrap();
+        let last = Fr::from_str_vartime(
+            "13409242754315411433193860530743374419854094495153957441316635981078068351329",
+        ).unwrap();
+        assert_eq!(rc[0][0], first);
+        assert_eq!(rc[POSEIDON_ROUNDS_F + POSEIDON_ROUNDS_P - 1][POSEIDON_WIDTH - 1], last);
+    }
+
+    #[test]
+    fn test_mds_matrix_is_invertible() {
+        let m = mds_matrix::<Fr>();
+        let minor = |r0: usize, r1: usize, c0: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+
+        // Cauchy matrices are MDS: every entry and every 2x2 minor is nonzero.
+        for (r0, r1) in [(0, 1), (0, 2), (1, 2)] {
+            for (c0, c1) in [(0, 1), (0, 2), (1, 2)] {
+                assert_ne!(minor(r0, r1, c0, c1), Fr::ZERO);
+            }
+        }
+        assert!(m.iter().flatten().all(|entry| *entry != Fr::ZERO));
+
+        let det = m[0][0] * minor(1, 2, 1, 2) - m[0][1] * minor(1, 2, 0, 2) + m[0][2] * minor(1, 2, 0, 1);
+        assert_ne!(det, Fr::ZERO);
+    }
+
+    #[test]
+    fn test_constants_are_parsed_once() {
+        assert!(std::ptr::eq(round_constants::<Fr>(), round_constants::<Fr>()));
+        assert!(std::ptr::eq(mds_matrix::<Fr>(), mds_matrix::<Fr>()));
+    }
+
+    #[test]
+    fn test_hash_matches_circomlib() {
+        // circomlibjs `poseidon([1, 2])`.
+        let expected = Fr::from_str_vartime(
+            "7853200120776062878684798364095072458815029376092732009249414926327459813530",
+        ).unwrap();
+        assert_eq!(poseidon_hash_native(&[Fr::from(1u64), Fr::from(2u64)]), expected);
+    }
+
+    #[test]
+    fn test_permutation_matches_reference_vector() {
+        // circomlib / poseidon-rs t = 3 permutation of [0, 1, 2].
+        let mut state = [Fr::from(0u64), Fr::from(1u64), Fr::from(2u64)];
+        poseidon_permute_native(&mut state);
+        assert_eq!(state, [
+            field_from_hex("0x115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a"),
+            field_from_hex("0x0fca49b798923ab0239de1c9e7a4a9a2210312b6a2f616d18b5a87f9b628ae29"),
+            field_from_hex("0x0e7ae82e40091e63cbd4f16a6d16310b3729d4b6e138fcf54110e2867045a30c"),
+        ]);
+    }
+
+    #[derive(Clone)]
+    struct HashTestConfig {
+        poseidon: PoseidonConfig,
+        instance: Column<Instance>,
+    }
+
+    struct HashTestCircuit {
+        inputs: [Value<Fr>; POSEIDON_RATE],
+    }
+
+    impl Circuit<Fr> for HashTestCircuit {
+        type Config = HashTestConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self { inputs: [Value::unknown(); POSEIDON_RATE] }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let state = [meta.advice_column(), meta.advice_column(), meta.advice_column()];
+            let round_constants = [meta.fixed_column(), meta.fixed_column(), meta.fixed_column()];
+            let constants = meta.fixed_column();
+            meta.enable_constant(constants);
+            let instance = meta.instance_column();
+            meta.enable_equality(instance);
+
+            HashTestConfig {
+                poseidon: PoseidonChip::configure(meta, state, round_constants),
+                instance,
+            }
+        }
+
+        fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<Fr>) -> Result<(), Error> {
+            let inputs = layouter.assign_region(
+                || "inputs",
+                |mut region| {
+                    self.inputs
+                        .iter()
+                        .enumerate()
+                        .map(|(i, v)| region.assign_advice(|| format!("input_{}", i), config.poseidon.state[i], 0, || *v))
+                        .collect::<Result<Vec<_>, _>>()
+                },
+            )?;
+
+            let chip = PoseidonChip::<Fr>::construct(config.poseidon.clone());
+            let output = chip.hash(layouter.namespace(|| "hash"), &inputs)?;
+            layouter.constrain_instance(output.cell(), config.instance, 0)
+        }
+    }
+
+    #[test]
+    fn test_chip_matches_native_permutation() {
+        use halo2_proofs::dev::MockProver;
+        use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0x5eed);
+        for _ in 0..4 {
+            let inputs = [Fr::random(&mut rng), Fr::random(&mut rng)];
+            let expected = poseidon_hash_native(&inputs);
+
+            let circuit = HashTestCircuit { inputs: inputs.map(Value::known) };
+            let prover = MockProver::run(7, &circuit, vec![vec![expected]]).unwrap();
+            prover.verify().unwrap();
+
+            let wrong = MockProver::run(7, &circuit, vec![vec![expected + Fr::ONE]]).unwrap();
+            assert!(wrong.verify().is_err());
+        }
+    }
+
+    #[test]
+    fn test_sponge_padding_separates_trailing_zeros() {
+        let a = Fr::from(7u64);
+        let one = poseidon_sponge_native(&[a]);
+        let two = poseidon_sponge_native(&[a, Fr::ZERO]);
+        let three = poseidon_sponge_native(&[a, Fr::ZERO, Fr::ZERO]);
+
+        assert_ne!(one, two);
+        assert_ne!(one, three);
+        assert_ne!(two, three);
+        assert_ne!(poseidon_sponge_native::<Fr>(&[]), poseidon_sponge_native(&[Fr::ZERO]));
+    }
+
+    struct SpongeTestCircuit {
+        inputs: Vec<Value<Fr>>,
+        /// Values the sponge absorbs instead of `inputs`, if set.
+        absorbed: Option<Vec<Value<Fr>>>,
+    }
+
+    impl Circuit<Fr> for SpongeTestCircuit {
+        type Config = HashTestConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                inputs: vec![Value::unknown(); self.inputs.len()],
+                absorbed: self.absorbed.as_ref().map(|values| vec![Value::unknown(); values.len()]),
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            HashTestCircuit::configure(meta)
+        }
+
+        fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<Fr>) -> Result<(), Error> {
+            let inputs = layouter.assign_region(
+                || "inputs",
+                |mut region| {
+                    self.inputs
+                        .iter()
+                        .enumerate()
+                        .map(|(i, v)| {
+                            let col = config.poseidon.state[i % POSEIDON_WIDTH];
+                            region.assign_advice(|| format!("input_{}", i), col, i / POSEIDON_WIDTH, || *v)
+                        })
+                        .collect::<Result<Vec<_>, _>>()
+                },
+            )?;
+
+            let chip = PoseidonChip::<Fr>::construct(config.poseidon.clone());
+            let output = match &self.absorbed {
+                Some(values) => chip.sponge(layouter.namespace(|| "sponge"), &inputs, values.clone())?,
+                None => chip.hash_sponge(layouter.namespace(|| "sponge"), &inputs)?,
+            };
+            layouter.constrain_instance(output.cell(), config.instance, 0)
+        }
+    }
+
+    #[test]
+    fn test_chip_sponge_matches_native() {
+        use halo2_proofs::dev::MockProver;
+
+        let a = Fr::from(7u64);
+        for inputs in [vec![a], vec![a, Fr::ZERO], vec![a, Fr::ZERO, Fr::ZERO]] {
+            let expected = poseidon_sponge_native(&inputs);
+            let circuit = SpongeTestCircuit { inputs: inputs.iter().copied().map(Value::known).collect(), absorbed: None };
+            let prover = MockProver::run(8, &circuit, vec![vec![expected]]).unwrap();
+            prover.verify().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_chip_sponge_rejects_tampered_input() {
+        use halo2_proofs::dev::MockProver;
+
+        let inputs = vec![Fr::from(7u64), Fr::from(8u64), Fr::from(9u64)];
+        let mut absorbed = inputs.clone();
+        absorbed[2] += Fr::ONE;
+
+        // The claimed output is the honest hash of what was absorbed, so only
+        // the link from the input cells into the sponge can catch the swap.
+        let circuit = SpongeTestCircuit {
+            inputs: inputs.iter().copied().map(Value::known).collect(),
+            absorbed: Some(absorbed.iter().copied().map(Value::known).collect()),
+        };
+        let prover = MockProver::run(8, &circuit, vec![vec![poseidon_sponge_native(&absorbed)]]).unwrap();
+        assert!(prover.verify().is_err());
+
+        let honest = SpongeTestCircuit {
+            inputs: inputs.iter().copied().map(Value::known).collect(),
+            absorbed: Some(inputs.iter().copied().map(Value::known).collect()),
+        };
+        MockProver::run(8, &honest, vec![vec![poseidon_sponge_native(&inputs)]]).unwrap().verify().unwrap();
+    }
 }
@@ -0,0 +1,176 @@
+use std::marker::PhantomData;
+use ff::PrimeField;
+use halo2_proofs::{
+    circuit::{AssignedCell, Chip, Layouter},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+
+/// Bit width of token amounts. Every amount-like cell is checked against it,
+/// so a value can never be `p - x` standing in for a negative number.
+pub const AMOUNT_BITS: usize = 64;
+
+#[derive(Clone, Debug)]
+pub struct RangeCheckConfig {
+    pub running_sum: Column<Advice>,
+    pub bit: Column<Advice>,
+    pub selector: Selector,
+}
+
+/// Proves `value < 2^num_bits` with a running sum over its bits:
+/// `z_0 = value`, `z_i = 2 * z_{i+1} + bit_i`, and `z_num_bits = 0`.
+pub struct RangeCheckChip<F: PrimeField> {
+    config: RangeCheckConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> Chip<F> for RangeCheckChip<F> {
+    type Config = RangeCheckConfig;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl<F: PrimeField> RangeCheckChip<F> {
+    pub fn construct(config: RangeCheckConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The final remainder is pinned with a constant, so the circuit must have
+    /// called `enable_constant` on some fixed column.
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        running_sum: Column<Advice>,
+        bit: Column<Advice>,
+    ) -> RangeCheckConfig {
+        let selector = meta.selector();
+        meta.enable_equality(running_sum);
+
+        meta.create_gate("range_check_bit", |meta| {
+            let s = meta.query_selector(selector);
+            let z = meta.query_advice(running_sum, Rotation::cur());
+            let z_next = meta.query_advice(running_sum, Rotation::next());
+            let bit = meta.query_advice(bit, Rotation::cur());
+
+            let one = Expression::Constant(F::ONE);
+            let two = Expression::Constant(F::from(2u64));
+
+            vec![
+                s.clone() * bit.clone() * (one - bit.clone()),
+                s * (z - z_next * two - bit),
+            ]
+        });
+
+        RangeCheckConfig {
+            running_sum,
+            bit,
+            selector,
+        }
+    }
+
+    pub fn range_check(
+        &self,
+        mut layouter: impl Layouter<F>,
+        value: &AssignedCell<F, F>,
+        num_bits: usize,
+    ) -> Result<(), Error> {
+        let config = &self.config;
+
+        layouter.assign_region(
+            || format!("range check {} bits", num_bits),
+            |mut region| {
+                value.copy_advice(|| "z_0", &mut region, config.running_sum, 0)?;
+
+                let mut z = value.value().copied();
+                for i in 0..num_bits {
+                    config.selector.enable(&mut region, i)?;
+
+                    let bit = z.map(|z| F::from(bool::from(z.is_odd()) as u64));
+                    region.assign_advice(|| format!("bit_{}", i), config.bit, i, || bit)?;
+
+                    z = z.zip(bit).map(|(z, bit)| (z - bit) * F::TWO_INV);
+                    if i + 1 < num_bits {
+                        region.assign_advice(|| format!("z_{}", i + 1), config.running_sum, i + 1, || z)?;
+                    }
+                }
+
+                // For an out-of-range value the honest remainder here is
+                // nonzero, so the last bit row cannot be satisfied.
+                region.assign_advice_from_constant(|| "z_final", config.running_sum, num_bits, F::ZERO)?;
+                Ok(())
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ff::Field;
+    use halo2_proofs::{
+        circuit::{SimpleFloorPlanner, Value},
+        dev::MockProver,
+        halo2curves::bn256::Fr,
+        plonk::Circuit,
+    };
+
+    struct RangeTestCircuit {
+        value: Value<Fr>,
+    }
+
+    impl Circuit<Fr> for RangeTestCircuit {
+        type Config = RangeCheckConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self { value: Value::unknown() }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let running_sum = meta.advice_column();
+            let bit = meta.advice_column();
+            let constants = meta.fixed_column();
+            meta.enable_constant(constants);
+            RangeCheckChip::configure(meta, running_sum, bit)
+        }
+
+        fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<Fr>) -> Result<(), Error> {
+            let value = layouter.assign_region(
+                || "value",
+                |mut region| region.assign_advice(|| "value", config.running_sum, 0, || self.value),
+            )?;
+            RangeCheckChip::construct(config).range_check(layouter.namespace(|| "range"), &value, AMOUNT_BITS)
+        }
+    }
+
+    fn check(value: Fr) -> bool {
+        let circuit = RangeTestCircuit { value: Value::known(value) };
+        MockProver::run(8, &circuit, vec![]).unwrap().verify().is_ok()
+    }
+
+    #[test]
+    fn test_accepts_u64_values() {
+        assert!(check(Fr::ZERO));
+        assert!(check(Fr::from(1_000_000_000_000_000_000u64)));
+        assert!(check(Fr::from(u64::MAX)));
+    }
+
+    #[test]
+    fn test_rejects_wrapped_values() {
+        let two_pow_64 = Fr::from(u64::MAX) + Fr::ONE;
+        assert!(!check(two_pow_64));
+
+        // A "negative" amount, e.g. a fee larger than the deposit.
+        assert!(!check(-Fr::ONE));
+        assert!(!check(Fr::from(5u64) - Fr::from(7u64)));
+    }
+}
@@ -2,66 +2,161 @@ use wasm_bindgen::prelude::*;
 use serde::{Serialize, Deserialize};
 use halo2_proofs::{
     halo2curves::bn256::{Bn256, Fr, G1Affine},
-    plonk::{keygen_pk, keygen_vk, create_proof, verify_proof, Circuit, ProvingKey},
-    poly::{
-        commitment::Params,
-        kzg::{
-            commitment::{KZGCommitmentScheme, ParamsKZG},
-            multiopen::{ProverSHPLONK, VerifierSHPLONK},
-            strategy::SingleStrategy,
-        },
-    },
-    transcript::{Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer},
-    SerdeFormat,
+    plonk::{ProvingKey, VerifyingKey},
+    poly::{commitment::Params, kzg::commitment::ParamsKZG},
 };
-use rand::rngs::OsRng;
-use sha2::{Sha256, Digest};
 use std::sync::OnceLock;
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
-use crate::withdrawal_circuit::{WithdrawalCircuit, WithdrawalWitness, WithdrawalPublicInputs, MERKLE_DEPTH};
-use crate::association_circuit::{AssociationCircuit, AssociationWitness, AssociationPublicInputs, ASSOCIATION_DEPTH};
+use crate::backend::{Halo2Backend, ProvingBackend};
+use crate::encoding::field_to_bytes;
+use crate::keygen::{check_key_fingerprint, read_circuit_pk, read_circuit_vk};
+use crate::CircuitError;
+use crate::withdrawal_circuit::{zero_hashes, WithdrawalCircuit, WithdrawalWitness, WithdrawalPublicInputs, MERKLE_DEPTH};
+use crate::association_circuit::{AssociationCircuit, AssociationWitness};
 
 static PARAMS: OnceLock<ParamsKZG<Bn256>> = OnceLock::new();
-static PK: OnceLock<ProvingKey<G1Affine>> = OnceLock::new();
 static ASSOC_PK: OnceLock<ProvingKey<G1Affine>> = OnceLock::new();
 
 const PARAMS_BYTES: &[u8] = include_bytes!("params.bin");
 const PK_BYTES: &[u8] = include_bytes!("withdrawal_pk.bin");
+const VK_BYTES: &[u8] = include_bytes!("withdrawal_vk.bin");
 const ASSOC_PK_BYTES: &[u8] = include_bytes!("association_pk.bin");
 
 const K: u32 = 13; 
 
+/// Variant used when a request does not name one.
+pub const DEFAULT_VARIANT: &str = "withdrawal";
+
+/// A withdrawal circuit build with its own embedded keys. All variants share
+/// `PARAMS`. The verifying key is read from its own file, so verify-only
+/// callers never decode the much larger proving key.
+struct CircuitVariant {
+    id: &'static str,
+    pk_bytes: &'static [u8],
+    vk_bytes: &'static [u8],
+    pk: OnceLock<ProvingKey<G1Affine>>,
+    vk: OnceLock<VerifyingKey<G1Affine>>,
+}
+
+static VARIANTS: [CircuitVariant; 1] = [CircuitVariant {
+    id: DEFAULT_VARIANT,
+    pk_bytes: PK_BYTES,
+    vk_bytes: VK_BYTES,
+    pk: OnceLock::new(),
+    vk: OnceLock::new(),
+}];
+
+impl CircuitVariant {
+    fn lookup(id: Option<&str>) -> Result<&'static CircuitVariant, String> {
+        let id = id.unwrap_or(DEFAULT_VARIANT);
+        VARIANTS.iter().find(|v| v.id == id).ok_or_else(|| {
+            let known: Vec<_> = VARIANTS.iter().map(|v| v.id).collect();
+            format!("Unknown circuit variant {:?}; known variants: {:?}", id, known)
+        })
+    }
+
+    fn pk(&self) -> &ProvingKey<G1Affine> {
+        self.pk.get_or_init(|| self.load_pk().expect("Failed to read PK"))
+    }
+
+    fn vk(&self) -> &VerifyingKey<G1Affine> {
+        self.vk.get_or_init(|| self.load_vk().expect("Failed to read VK"))
+    }
+
+    fn load_pk(&self) -> Result<ProvingKey<G1Affine>, String> {
+        read_circuit_pk::<WithdrawalCircuit<Fr>, _>(self.pk_bytes).map_err(|e| e.to_string())
+    }
+
+    fn load_vk(&self) -> Result<VerifyingKey<G1Affine>, String> {
+        read_circuit_vk::<WithdrawalCircuit<Fr>, _>(self.vk_bytes).map_err(|e| e.to_string())
+    }
+}
+
 #[wasm_bindgen(start)]
 pub fn init() {
     #[cfg(feature = "console_error_panic_hook")]
     console_error_panic_hook::set_once();
+
+    // Only the key headers are read here; the keys decode lazily on first use.
+    for variant in VARIANTS.iter() {
+        for bytes in [variant.pk_bytes, variant.vk_bytes] {
+            if let Err(e) = check_key_fingerprint::<WithdrawalCircuit<Fr>>(bytes) {
+                panic!("Embedded {} keys: {}", variant.id, e);
+            }
+        }
+    }
+    if let Err(e) = check_key_fingerprint::<AssociationCircuit<Fr>>(ASSOC_PK_BYTES) {
+        panic!("Embedded association keys: {}", e);
+    }
 }
 
 fn get_params() -> &'static ParamsKZG<Bn256> {
-    PARAMS.get_or_init(|| {
-        ParamsKZG::<Bn256>::read(&mut &PARAMS_BYTES[..]).expect("Failed to read params")
-    })
+    PARAMS.get_or_init(|| load_params().expect("Failed to read params"))
+}
+
+fn get_assoc_pk() -> &'static ProvingKey<G1Affine> {
+    ASSOC_PK.get_or_init(|| load_assoc_pk().expect("Failed to read Association PK"))
 }
 
-fn get_pk() -> &'static ProvingKey<G1Affine> {
-    PK.get_or_init(|| {
-        ProvingKey::<G1Affine>::read::<_, WithdrawalCircuit<Fr>>(
-            &mut &PK_BYTES[..],
-            SerdeFormat::RawBytes
-        ).expect("Failed to read PK")
-    })
+fn load_params() -> Result<ParamsKZG<Bn256>, String> {
+    ParamsKZG::<Bn256>::read(&mut &PARAMS_BYTES[..]).map_err(|e| e.to_string())
 }
 
-fn get_assoc_pk() -> &'static ProvingKey<G1Affine> {
-    ASSOC_PK.get_or_init(|| {
-        ProvingKey::<G1Affine>::read::<_, AssociationCircuit<Fr>>(
-            &mut &ASSOC_PK_BYTES[..],
-            SerdeFormat::RawBytes
-        ).expect("Failed to read Association PK")
-    })
+fn load_assoc_pk() -> Result<ProvingKey<G1Affine>, String> {
+    read_circuit_pk::<AssociationCircuit<Fr>, _>(ASSOC_PK_BYTES).map_err(|e| e.to_string())
+}
+
+/// Loads `cell` with `load` unless it is already populated.
+fn preload<T>(name: &str, cell: &OnceLock<T>, load: impl FnOnce() -> Result<T, String>) -> ComponentStatus {
+    let error = match cell.get() {
+        Some(_) => None,
+        None => match load() {
+            Ok(value) => {
+                let _ = cell.set(value);
+                None
+            }
+            Err(e) => Some(e),
+        },
+    };
+    ComponentStatus {
+        name: name.to_string(),
+        loaded: error.is_none(),
+        error,
+    }
 }
 
 #[derive(Serialize, Deserialize)]
+pub struct ComponentStatus {
+    pub name: String,
+    pub loaded: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PreflightResult {
+    pub success: bool,
+    pub components: Vec<ComponentStatus>,
+}
+
+/// Eagerly decodes the embedded params and proving keys so a host can report
+/// a broken bundle at startup instead of on the first proof.
+#[wasm_bindgen]
+pub fn preflight() -> String {
+    let mut components = vec![preload("params", &PARAMS, load_params)];
+    for variant in VARIANTS.iter() {
+        components.push(preload(&format!("{}_pk", variant.id), &variant.pk, || variant.load_pk()));
+        components.push(preload(&format!("{}_vk", variant.id), &variant.vk, || variant.load_vk()));
+    }
+    components.push(preload("association_pk", &ASSOC_PK, load_assoc_pk));
+
+    serde_json::to_string(&PreflightResult {
+        success: components.iter().all(|c| c.loaded),
+        components,
+    }).unwrap()
+}
+
+#[derive(Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
 pub struct ProofRequest {
     pub secret: Vec<u8>,
     pub nullifier_seed: Vec<u8>,
@@ -69,13 +164,21 @@ pub struct ProofRequest {
     pub leaf_index: u32,
     pub merkle_path: Vec<Vec<u8>>,
     pub path_indices: Vec<bool>,
+    /// Root as the 32-byte word the vault reports from `getLatestRoot`.
     pub merkle_root: Vec<u8>,
     pub recipient: Vec<u8>,
+    /// Vault and chain the withdrawal is for; the proof only verifies there.
+    pub vault_address: Vec<u8>,
+    pub chain_id: u64,
+    /// Registered circuit variant to prove with; `DEFAULT_VARIANT` if absent.
+    #[serde(default)]
+    pub variant: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
 pub struct ComplianceRequest {
     pub commitment: Vec<u8>,
+    pub secret: Vec<u8>,
     pub association_path: Vec<Vec<u8>>,
     pub path_indices: Vec<bool>,
     pub association_root: Vec<u8>,
@@ -88,6 +191,25 @@ pub struct ProofResult {
     pub nullifier_hash: Vec<u8>,
     pub public_inputs: Vec<Vec<u8>>,
     pub error: Option<String>,
+    #[serde(default)]
+    pub variant: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BatchVerifyItem {
+    pub proof: Vec<u8>,
+    #[serde(default)]
+    pub public_inputs: Vec<Vec<u8>>,
+    /// Variant the proof was made with, as on `ProofResult`.
+    #[serde(default)]
+    pub variant: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BatchVerifyResult {
+    pub results: Vec<bool>,
+    pub all_valid: bool,
+    pub error: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -107,15 +229,26 @@ pub fn generate_withdrawal_proof(request_json: &str) -> String {
         }
     };
 
+    serde_json::to_string(&prove_request(&request)).unwrap()
+}
+
+fn prove_request(request: &ProofRequest) -> ProofResult {
+    let variant = match CircuitVariant::lookup(request.variant.as_deref()) {
+        Ok(variant) => variant,
+        Err(e) => return failed_result(e),
+    };
+
     let mut secret = [0u8; 32];
     let mut nullifier_seed = [0u8; 32];
     let mut merkle_root = [0u8; 32];
     let mut recipient = [0u8; 20];
+    let mut vault_address = [0u8; 20];
 
     copy_bytes(&request.secret, &mut secret);
     copy_bytes(&request.nullifier_seed, &mut nullifier_seed);
     copy_bytes(&request.merkle_root, &mut merkle_root);
     copy_bytes_20(&request.recipient, &mut recipient);
+    copy_bytes_20(&request.vault_address, &mut vault_address);
 
     let merkle_path: Vec<[u8; 32]> = request.merkle_path
         .iter()
@@ -131,8 +264,6 @@ pub fn generate_withdrawal_proof(request_json: &str) -> String {
         path_indices.push(false);
     }
 
-    let nullifier_hash = compute_nullifier(&nullifier_seed, request.leaf_index);
-
     let witness = WithdrawalWitness {
         secret,
         nullifier_seed,
@@ -141,31 +272,106 @@ pub fn generate_withdrawal_proof(request_json: &str) -> String {
         merkle_path: pad_merkle_path(merkle_path),
         path_indices,
     };
-
-    let public_inputs = WithdrawalPublicInputs {
-        merkle_root,
-        nullifier: nullifier_hash,
-        recipient,
-        amount: request.amount,
+    // The witness owns copies now; the request is wiped when it drops.
+    secret.zeroize();
+    nullifier_seed.zeroize();
+
+    // The circuit binds the root and nullifier it derives from the witness, so
+    // a request whose claimed root differs could never produce a valid proof.
+    let public_inputs = witness
+        .public_inputs::<Fr>(recipient)
+        .bound_to(vault_address, request.chain_id);
+    let words = match public_inputs.evm_words() {
+        Ok(words) => words,
+        Err(e) => return failed_result(e.to_string()),
+    };
+    if words[0] != merkle_root {
+        return failed_result(CircuitError::RootMismatch.to_string());
+    }
+    let instances = match public_inputs.instances::<Fr>() {
+        Ok(instances) => instances,
+        Err(e) => return failed_result(e.to_string()),
     };
 
     let circuit = WithdrawalCircuit::<Fr>::new(witness, public_inputs.clone());
 
-    match generate_real_proof(circuit) {
-        Ok(proof_bytes) => {
-             serde_json::to_string(&ProofResult {
-                success: true,
-                proof: proof_bytes,
-                nullifier_hash: nullifier_hash.to_vec(),
-                public_inputs: vec![
-                    public_inputs.merkle_root.to_vec(),
-                    public_inputs.nullifier.to_vec(),
-                    public_inputs.recipient.to_vec(),
-                ],
-                error: None,
-            }).unwrap()
+    match generate_real_proof(circuit, variant, &instances) {
+        Ok(proof_bytes) => ProofResult {
+            success: true,
+            proof: proof_bytes,
+            nullifier_hash: words[1].to_vec(),
+            public_inputs: words.iter().map(|word| word.to_vec()).collect(),
+            error: None,
+            variant: Some(variant.id.to_string()),
+        },
+        Err(e) => failed_result(e),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BatchProofResult {
+    pub results: Vec<ProofResult>,
+    pub all_succeeded: bool,
+}
+
+/// Collects withdrawal requests and proves them in one call.
+///
+/// Every request in the session proves against the same loaded params and
+/// proving keys, so the embedded keys are deserialized at most once no matter
+/// how many requests are queued. Requests are proved in order on the calling
+/// thread; to use several cores, give each web worker its own session.
+#[wasm_bindgen]
+pub struct BatchProofSession {
+    max_requests: usize,
+    requests: Vec<Result<ProofRequest, String>>,
+}
+
+#[wasm_bindgen]
+impl BatchProofSession {
+    /// Opens a session that accepts up to `max_requests` requests.
+    pub fn begin_batch(max_requests: usize) -> BatchProofSession {
+        BatchProofSession {
+            max_requests,
+            requests: Vec::new(),
+        }
+    }
+
+    /// Queues a `ProofRequest` JSON string. Returns `false` once the session
+    /// is full. A request that fails to parse still takes its slot and is
+    /// reported as a failed result by `finalize`, so results stay aligned
+    /// with the order requests were added in.
+    pub fn add_request(&mut self, request_json: &str) -> bool {
+        if self.requests.len() >= self.max_requests {
+            return false;
         }
-        Err(e) => error_result(e),
+        let request = serde_json::from_str(request_json).map_err(|e| format!("Parse error: {}", e));
+        self.requests.push(request);
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// Proves every queued request and returns a `BatchProofResult` JSON
+    /// string with one `ProofResult` per request.
+    pub fn finalize(self) -> String {
+        let results: Vec<ProofResult> = self.requests
+            .iter()
+            .map(|request| match request {
+                Ok(request) => prove_request(request),
+                Err(e) => failed_result(e.clone()),
+            })
+            .collect();
+
+        serde_json::to_string(&BatchProofResult {
+            all_succeeded: !results.is_empty() && results.iter().all(|r| r.success),
+            results,
+        }).unwrap()
     }
 }
 
@@ -184,9 +390,11 @@ pub fn generate_compliance_proof(request_json: &str) -> String {
     };
 
     let mut commitment = [0u8; 32];
+    let mut secret = [0u8; 32];
     let mut association_root = [0u8; 32];
 
     copy_bytes(&request.commitment, &mut commitment);
+    copy_bytes(&request.secret, &mut secret);
     copy_bytes(&request.association_root, &mut association_root);
 
     let association_path: Vec<[u8; 32]> = request.association_path
@@ -198,39 +406,41 @@ pub fn generate_compliance_proof(request_json: &str) -> String {
         })
         .collect();
 
-    let mut path_indices = request.path_indices.clone();
-    while path_indices.len() < ASSOCIATION_DEPTH {
-        path_indices.push(false);
-    }
-
-    let params = get_params();
-    let pk = get_assoc_pk();
-
     let witness = AssociationWitness {
         commitment,
-        association_path: pad_association_path(association_path),
-        path_indices,
+        secret,
+        association_path,
+        path_indices: request.path_indices.clone(),
     };
+    secret.zeroize();
 
-    let public_inputs = AssociationPublicInputs {
-        association_root,
-        commitment_hash: commitment, 
+    let public_inputs = witness.public_inputs();
+    if public_inputs.association_root != association_root {
+        return serde_json::to_string(&ComplianceResult {
+            success: false,
+            proof: vec![],
+            public_inputs: vec![],
+            error: Some("Commitment is not in the association set at the given root".into()),
+        }).unwrap();
+    }
+    let instances = match public_inputs.instances() {
+        Ok(instances) => instances,
+        Err(e) => {
+            return serde_json::to_string(&ComplianceResult {
+                success: false,
+                proof: vec![],
+                public_inputs: vec![],
+                error: Some(e.to_string()),
+            }).unwrap();
+        }
     };
 
+    let params = get_params();
+    let pk = get_assoc_pk();
     let circuit = AssociationCircuit::<Fr>::new(witness, public_inputs.clone());
 
-    let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
-    
-    match create_proof::<KZGCommitmentScheme<Bn256>, ProverSHPLONK<'_, Bn256>, _, _, _, _>(
-        params,
-        pk,
-        &[circuit],
-        &[&[]], 
-        OsRng,
-        &mut transcript,
-    ) {
-        Ok(_) => {
-            let proof = transcript.finalize();
+    match Halo2Backend::prover(params, pk).prove(circuit, &[&instances]) {
+        Ok(proof) => {
              serde_json::to_string(&ComplianceResult {
                 success: true,
                 proof,
@@ -245,28 +455,18 @@ pub fn generate_compliance_proof(request_json: &str) -> String {
             success: false,
             proof: vec![],
             public_inputs: vec![],
-            error: Some(format!("Proof generation failed: {:?}", e)),
+            error: Some(e.to_string()),
         }).unwrap()
     }
 }
 
-fn generate_real_proof(circuit: WithdrawalCircuit<Fr>) -> Result<Vec<u8>, String> {
+fn generate_real_proof(circuit: WithdrawalCircuit<Fr>, variant: &CircuitVariant, instances: &[Fr]) -> Result<Vec<u8>, String> {
     let params = get_params();
-    let pk = get_pk();
-
-    let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
-    
-    create_proof::<KZGCommitmentScheme<Bn256>, ProverSHPLONK<'_, Bn256>, _, _, _, _>(
-        params,
-        pk,
-        &[circuit],
-        &[&[]],
-        OsRng,
-        &mut transcript,
-    ).map_err(|e| format!("create_proof failed: {:?}", e))?;
+    let pk = variant.pk();
 
-    let proof = transcript.finalize();
-    Ok(proof)
+    Halo2Backend::prover(params, pk)
+        .prove(circuit, &[instances])
+        .map_err(|e| e.to_string())
 }
 
 #[wasm_bindgen]
@@ -277,37 +477,102 @@ pub fn verify_withdrawal_proof(proof_json: &str) -> bool {
             if !r.success || r.proof.is_empty() {
                 return false;
             }
+
+            let variant = match CircuitVariant::lookup(r.variant.as_deref()) {
+                Ok(variant) => variant,
+                Err(_) => return false,
+            };
             
-            let params = get_params();
-            let empty_circuit = WithdrawalCircuit::<Fr>::default();
-            
-            let vk = match keygen_vk(params, &empty_circuit) {
-                Ok(vk) => vk,
+            let instances = match withdrawal_instances(&r.public_inputs) {
+                Ok(instances) => instances,
                 Err(_) => return false,
             };
+            if crate::check_instance_shape(&WithdrawalCircuit::<Fr>::num_instances(), &[&instances]).is_err() {
+                return false;
+            }
 
-            let mut transcript = Blake2bRead::<_, G1Affine, Challenge255<_>>::init(&r.proof[..]);
-            
-            verify_proof::<KZGCommitmentScheme<Bn256>, VerifierSHPLONK<'_, Bn256>, _, _, _>(
-                params,
-                &vk,
-                SingleStrategy::new(params),
-                &[&[]],
-                &mut transcript,
-            ).is_ok()
+            Halo2Backend::verifier(get_params(), variant.vk())
+                .verify(&r.proof, &[&instances])
+                .is_ok()
         }
         Err(_) => false,
     }
 }
 
-fn compute_nullifier(seed: &[u8; 32], leaf_index: u32) -> [u8; 32] {
-    let mut hasher = Sha256::new();
-    hasher.update(seed);
-    hasher.update(&leaf_index.to_le_bytes());
-    let result = hasher.finalize();
-    let mut output = [0u8; 32];
-    output.copy_from_slice(&result);
-    output
+/// Verifies an array of `{proof, public_inputs, variant}`, one accumulated
+/// check per variant against that variant's key. An item naming an unknown
+/// variant, or whose `public_inputs` don't decode to the withdrawal instance
+/// shape, is reported invalid without being verified.
+#[wasm_bindgen]
+pub fn verify_withdrawal_proofs_batch(proofs_json: &str) -> String {
+    let items: Vec<BatchVerifyItem> = match serde_json::from_str(proofs_json) {
+        Ok(items) => items,
+        Err(e) => {
+            return serde_json::to_string(&BatchVerifyResult {
+                results: vec![],
+                all_valid: false,
+                error: Some(format!("Parse error: {}", e)),
+            }).unwrap();
+        }
+    };
+
+    let prepared: Vec<Option<(&'static CircuitVariant, Vec<Fr>)>> = items
+        .iter()
+        .map(|item| {
+            let variant = CircuitVariant::lookup(item.variant.as_deref()).ok()?;
+            let instances = withdrawal_instances(&item.public_inputs).ok()?;
+            crate::check_instance_shape(&WithdrawalCircuit::<Fr>::num_instances(), &[&instances]).ok()?;
+            Some((variant, instances))
+        })
+        .collect();
+
+    let mut results = vec![false; items.len()];
+    for variant in VARIANTS.iter() {
+        let (indices, columns): (Vec<usize>, Vec<[&[Fr]; 1]>) = prepared
+            .iter()
+            .enumerate()
+            .filter_map(|(index, prepared)| match prepared {
+                Some((v, instances)) if std::ptr::eq(*v, variant) => Some((index, [instances.as_slice()])),
+                _ => None,
+            })
+            .unzip();
+        if indices.is_empty() {
+            continue;
+        }
+
+        let batch: Vec<(&[u8], &[&[Fr]])> = indices
+            .iter()
+            .zip(&columns)
+            .map(|(&index, column)| (&items[index].proof[..], &column[..]))
+            .collect();
+        let verified = Halo2Backend::verifier(get_params(), variant.vk()).verify_batch(&batch);
+        for (index, ok) in indices.into_iter().zip(verified) {
+            results[index] = ok;
+        }
+    }
+
+    serde_json::to_string(&BatchVerifyResult {
+        all_valid: !results.is_empty() && results.iter().all(|ok| *ok),
+        results,
+        error: None,
+    }).unwrap()
+}
+
+/// Instance column for the `public_inputs` of a `ProofResult`: merkle root,
+/// nullifier, recipient, amount, vault address and chain id, each as the
+/// 32-byte word `PrivacyVault` passes to its verifier.
+fn withdrawal_instances(public_inputs: &[Vec<u8>]) -> Result<Vec<Fr>, String> {
+    let words: Vec<[u8; 32]> = public_inputs
+        .iter()
+        .map(|word| word.as_slice().try_into().map_err(|_| "Public inputs must be 32-byte words".to_string()))
+        .collect::<Result<_, _>>()?;
+    let words: [[u8; 32]; 6] = words
+        .try_into()
+        .map_err(|_| format!("Expected 6 public inputs, got {}", public_inputs.len()))?;
+
+    WithdrawalPublicInputs::from_evm_words(&words)
+        .and_then(|public_inputs| public_inputs.instances::<Fr>())
+        .map_err(|e| e.to_string())
 }
 
 fn copy_bytes(src: &[u8], dst: &mut [u8; 32]) {
@@ -321,25 +586,233 @@ fn copy_bytes_20(src: &[u8], dst: &mut [u8; 20]) {
 }
 
 fn pad_merkle_path(mut path: Vec<[u8; 32]>) -> Vec<[u8; 32]> {
+    let zeros = zero_hashes::<Fr>(MERKLE_DEPTH);
     while path.len() < MERKLE_DEPTH {
-        path.push([0u8; 32]);
+        path.push(field_to_bytes(zeros[path.len()]));
     }
     path
 }
 
-fn pad_association_path(mut path: Vec<[u8; 32]>) -> Vec<[u8; 32]> {
-    while path.len() < ASSOCIATION_DEPTH {
-        path.push([0u8; 32]);
-    }
-    path
-}
-
-fn error_result(msg: String) -> String {
-    serde_json::to_string(&ProofResult {
+fn failed_result(msg: String) -> ProofResult {
+    ProofResult {
         success: false,
         proof: vec![],
         nullifier_hash: vec![],
         public_inputs: vec![],
         error: Some(msg),
-    }).unwrap()
+        variant: None,
+    }
+}
+
+fn error_result(msg: String) -> String {
+    serde_json::to_string(&failed_result(msg)).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    fn withdrawal_request(seed: u8) -> String {
+        let witness = WithdrawalWitness {
+            secret: [seed; 32],
+            nullifier_seed: [seed.wrapping_add(1); 32],
+            amount: 1_000 + seed as u64,
+            leaf_index: seed as u32,
+            // Left empty here and in the request, so both sides pad it with
+            // the same empty-subtree hashes.
+            merkle_path: vec![],
+            path_indices: (0..MERKLE_DEPTH).map(|level| ((seed as u32) >> level) & 1 == 1).collect(),
+        };
+        let public_inputs = witness.public_inputs::<Fr>([seed.wrapping_add(0x10); 20]).bound_to([0xa0; 20], 1);
+        let words = public_inputs.evm_words().unwrap();
+
+        serde_json::to_string(&ProofRequest {
+            secret: witness.secret.to_vec(),
+            nullifier_seed: witness.nullifier_seed.to_vec(),
+            amount: witness.amount,
+            leaf_index: witness.leaf_index,
+            merkle_path: vec![],
+            path_indices: witness.path_indices.clone(),
+            merkle_root: words[0].to_vec(),
+            recipient: public_inputs.recipient.to_vec(),
+            vault_address: public_inputs.vault_address.to_vec(),
+            chain_id: public_inputs.chain_id,
+            variant: None,
+        }).unwrap()
+    }
+
+    #[wasm_bindgen_test]
+    fn test_preflight_loads_embedded_keys() {
+        let status: PreflightResult = serde_json::from_str(&preflight()).unwrap();
+        assert!(status.success);
+
+        let names: Vec<_> = status.components.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["params", "withdrawal_pk", "withdrawal_vk", "association_pk"]);
+        assert!(status.components.iter().all(|c| c.loaded && c.error.is_none()));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_each_variant_proves_and_verifies() {
+        for variant in VARIANTS.iter() {
+            let mut request: ProofRequest = serde_json::from_str(&withdrawal_request(1)).unwrap();
+            request.variant = Some(variant.id.to_string());
+
+            let proof = generate_withdrawal_proof(&serde_json::to_string(&request).unwrap());
+            let result: ProofResult = serde_json::from_str(&proof).unwrap();
+            assert!(result.success, "{}: {:?}", variant.id, result.error);
+            assert_eq!(result.variant.as_deref(), Some(variant.id));
+            assert!(verify_withdrawal_proof(&proof));
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn test_tampered_public_nullifier_is_rejected() {
+        let mut result: ProofResult = serde_json::from_str(&generate_withdrawal_proof(&withdrawal_request(2))).unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert!(verify_withdrawal_proof(&serde_json::to_string(&result).unwrap()));
+
+        result.public_inputs[1][31] ^= 1;
+        result.nullifier_hash = result.public_inputs[1].clone();
+        assert!(!verify_withdrawal_proof(&serde_json::to_string(&result).unwrap()));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_proof_for_another_vault_is_rejected() {
+        let result: ProofResult = serde_json::from_str(&generate_withdrawal_proof(&withdrawal_request(3))).unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.public_inputs[4][..12], [0u8; 12]);
+        assert_eq!(result.public_inputs[4][12..], [0xa0; 20]);
+
+        let mut other_vault = serde_json::from_str::<ProofResult>(&serde_json::to_string(&result).unwrap()).unwrap();
+        other_vault.public_inputs[4][12..].copy_from_slice(&[0xb0; 20]);
+        assert!(!verify_withdrawal_proof(&serde_json::to_string(&other_vault).unwrap()));
+
+        let mut other_chain = result;
+        other_chain.public_inputs[5][31] = 10;
+        assert!(!verify_withdrawal_proof(&serde_json::to_string(&other_chain).unwrap()));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_root_mismatch_is_reported() {
+        let mut request: ProofRequest = serde_json::from_str(&withdrawal_request(1)).unwrap();
+        request.merkle_root[0] ^= 1;
+
+        let result: ProofResult = serde_json::from_str(
+            &generate_withdrawal_proof(&serde_json::to_string(&request).unwrap()),
+        ).unwrap();
+        assert!(!result.success);
+        assert_eq!(result.error.unwrap(), CircuitError::RootMismatch.to_string());
+    }
+
+    /// The embedded keys agree with each other and with a vk generated now
+    /// from the circuit code, so a stale pk/vk pair can't pass by matching
+    /// only itself.
+    #[wasm_bindgen_test]
+    fn test_embedded_vk_matches_proving_key() {
+        use halo2_proofs::plonk::keygen_vk;
+
+        let fresh = keygen_vk(get_params(), &WithdrawalCircuit::<Fr>::default()).unwrap();
+        for variant in VARIANTS.iter() {
+            assert_eq!(variant.vk().transcript_repr(), variant.pk().get_vk().transcript_repr(), "{}", variant.id);
+            assert_eq!(variant.vk().transcript_repr(), fresh.transcript_repr(), "{}", variant.id);
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn test_cached_vk_beats_per_call_keygen() {
+        use halo2_proofs::plonk::keygen_vk;
+
+        let proof = generate_withdrawal_proof(&withdrawal_request(3));
+        let result: ProofResult = serde_json::from_str(&proof).unwrap();
+        let instances = withdrawal_instances(&result.public_inputs).unwrap();
+        assert!(verify_withdrawal_proof(&proof));
+
+        let started = js_sys::Date::now();
+        for _ in 0..4 {
+            let vk = keygen_vk(get_params(), &WithdrawalCircuit::<Fr>::default()).unwrap();
+            assert!(Halo2Backend::verifier(get_params(), &vk).verify(&result.proof, &[&instances]).is_ok());
+        }
+        let per_call_keygen = js_sys::Date::now() - started;
+
+        let started = js_sys::Date::now();
+        for _ in 0..4 {
+            assert!(verify_withdrawal_proof(&proof));
+        }
+        let cached = js_sys::Date::now() - started;
+
+        wasm_bindgen_test::console_log!("4 verifications: keygen_vk per call {}ms, cached vk {}ms", per_call_keygen, cached);
+        assert!(cached < per_call_keygen);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_unknown_variant_is_rejected() {
+        let mut request: ProofRequest = serde_json::from_str(&withdrawal_request(1)).unwrap();
+        request.variant = Some("denomination-1eth".to_string());
+
+        let result: ProofResult = serde_json::from_str(
+            &generate_withdrawal_proof(&serde_json::to_string(&request).unwrap()),
+        ).unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("Unknown circuit variant \"denomination-1eth\""));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_batch_session_proves_each_request() {
+        let mut session = BatchProofSession::begin_batch(4);
+        for seed in 0..4u8 {
+            assert!(session.add_request(&withdrawal_request(seed)));
+        }
+        assert!(!session.add_request(&withdrawal_request(4)));
+        assert_eq!(session.len(), 4);
+
+        let batch: BatchProofResult = serde_json::from_str(&session.finalize()).unwrap();
+        assert!(batch.all_succeeded);
+        assert_eq!(batch.results.len(), 4);
+        for (seed, result) in batch.results.iter().enumerate() {
+            assert!(result.success, "request {}: {:?}", seed, result.error);
+            assert!(verify_withdrawal_proof(&serde_json::to_string(result).unwrap()));
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn test_verify_withdrawal_proofs_batch() {
+        let mut items: Vec<BatchVerifyItem> = (0..8u8)
+            .map(|seed| {
+                let result: ProofResult = serde_json::from_str(&generate_withdrawal_proof(&withdrawal_request(seed))).unwrap();
+                assert!(result.success, "{:?}", result.error);
+                BatchVerifyItem {
+                    proof: result.proof,
+                    public_inputs: result.public_inputs,
+                    variant: result.variant,
+                }
+            })
+            .collect();
+
+        let valid: BatchVerifyResult = serde_json::from_str(
+            &verify_withdrawal_proofs_batch(&serde_json::to_string(&items).unwrap()),
+        ).unwrap();
+        assert_eq!(valid.results, vec![true; 8]);
+        assert!(valid.all_valid);
+
+        let last = items[5].proof.len() - 1;
+        items[5].proof[last] ^= 1;
+        let mixed: BatchVerifyResult = serde_json::from_str(
+            &verify_withdrawal_proofs_batch(&serde_json::to_string(&items).unwrap()),
+        ).unwrap();
+        let mut expected = vec![true; 8];
+        expected[5] = false;
+        assert_eq!(mixed.results, expected);
+        assert!(!mixed.all_valid);
+
+        // A short instance column or an unknown variant fails that item only.
+        items[2].public_inputs.pop();
+        items[6].variant = Some("no-such-variant".into());
+        let rejected: BatchVerifyResult = serde_json::from_str(
+            &verify_withdrawal_proofs_batch(&serde_json::to_string(&items).unwrap()),
+        ).unwrap();
+        expected[2] = false;
+        expected[6] = false;
+        assert_eq!(rejected.results, expected);
+    }
 }
@@ -1,25 +1,43 @@
 use std::marker::PhantomData;
 use ff::PrimeField;
 use halo2_proofs::{
-    circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
+    circuit::{Layouter, SimpleFloorPlanner, Value},
     plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Expression, Fixed, Instance, Selector},
     poly::Rotation,
 };
 use serde::{Serialize, Deserialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::encoding::{bytes_to_field, field_from_bytes, field_from_canonical_bytes, field_to_bytes};
+use crate::merkle::fold_merkle_path;
+use crate::merkle_path::{MerklePathChip, MerklePathConfig};
+use crate::poseidon::{poseidon_hash_native, PoseidonChip, PoseidonConfig};
+use crate::range_check::{RangeCheckChip, RangeCheckConfig, AMOUNT_BITS};
+use crate::CircuitError;
 
 pub const MERKLE_DEPTH: usize = 20;
 
+/// Rows of the instance column; see `WithdrawalPublicInputs::instances`.
+const MERKLE_ROOT_ROW: usize = 0;
+const NULLIFIER_ROW: usize = 1;
+const RECIPIENT_ROW: usize = 2;
+const AMOUNT_ROW: usize = 3;
+const VAULT_ROW: usize = 4;
+const CHAIN_ID_ROW: usize = 5;
+
 #[derive(Clone, Debug)]
 pub struct WithdrawalConfig {
     pub advice: [Column<Advice>; 5],
     pub fixed: Column<Fixed>,
     pub instance: Column<Instance>,
-    pub s_hash: Selector,
-    pub s_merkle: Selector,
-    pub s_nullifier: Selector,
+    pub s_recipient: Selector,
+    pub merkle_path: MerklePathConfig,
+    pub poseidon: PoseidonConfig,
+    pub range_check: RangeCheckConfig,
 }
 
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+/// Wiped on drop: the secret, seed and position identify the deposit.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
 pub struct WithdrawalWitness {
     pub secret: [u8; 32],
     pub nullifier_seed: [u8; 32],
@@ -29,12 +47,162 @@ pub struct WithdrawalWitness {
     pub path_indices: Vec<bool>,
 }
 
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct WithdrawalPublicInputs {
     pub merkle_root: [u8; 32],
     pub nullifier: [u8; 32],
     pub recipient: [u8; 20],
     pub amount: u64,
+    /// Deployment the proof is for. Binding these stops a proof made for one
+    /// vault or chain from being replayed at another sharing the same root.
+    pub vault_address: [u8; 20],
+    pub chain_id: u64,
+}
+
+impl WithdrawalPublicInputs {
+    /// The same inputs, bound to the vault at `vault_address` on `chain_id`.
+    pub fn bound_to(mut self, vault_address: [u8; 20], chain_id: u64) -> Self {
+        self.vault_address = vault_address;
+        self.chain_id = chain_id;
+        self
+    }
+
+    /// Values of the instance column, in row order: merkle root, nullifier,
+    /// recipient, amount, vault address, chain id. Root and nullifier must be
+    /// canonical field encodings and the recipient must not be the zero
+    /// address, which the circuit rejects. Each value is the integer
+    /// `PrivacyVault` puts in the matching `publicInputs` word, so addresses
+    /// are read as big-endian `uint160`s.
+    pub fn instances<F: PrimeField>(&self) -> Result<Vec<F>, CircuitError> {
+        if self.recipient == [0u8; 20] {
+            return Err(CircuitError::InvalidInput("Recipient is the zero address".into()));
+        }
+        let merkle_root = field_from_canonical_bytes::<F>(&self.merkle_root)
+            .ok_or_else(|| CircuitError::InvalidInput("Merkle root is not a canonical field element".into()))?;
+        let nullifier = field_from_canonical_bytes::<F>(&self.nullifier)
+            .ok_or_else(|| CircuitError::InvalidInput("Nullifier is not a canonical field element".into()))?;
+
+        Ok(vec![
+            merkle_root,
+            nullifier,
+            address_field(&self.recipient),
+            F::from(self.amount),
+            address_field(&self.vault_address),
+            F::from(self.chain_id),
+        ])
+    }
+
+    /// The `bytes32[]` `PrivacyVault` passes to the verifier for these inputs:
+    /// each instance value as a 32-byte big-endian word.
+    pub fn evm_words(&self) -> Result<[[u8; 32]; 6], CircuitError> {
+        let instances = self.instances::<halo2_proofs::halo2curves::bn256::Fr>()?;
+        let mut words = [[0u8; 32]; 6];
+        for (word, value) in words.iter_mut().zip(instances) {
+            *word = field_to_bytes(value);
+            word.reverse();
+        }
+        Ok(words)
+    }
+
+    /// Inverse of `evm_words`. Address, amount and chain id words must fit
+    /// their types, and root and nullifier must be canonical field elements.
+    pub fn from_evm_words(words: &[[u8; 32]; 6]) -> Result<Self, CircuitError> {
+        let field_word = |word: &[u8; 32], name: &str| {
+            let mut bytes = *word;
+            bytes.reverse();
+            field_from_canonical_bytes::<halo2_proofs::halo2curves::bn256::Fr>(&bytes)
+                .map(|_| bytes)
+                .ok_or_else(|| CircuitError::InvalidInput(format!("{} is not a canonical field element", name)))
+        };
+        let address_word = |word: &[u8; 32], name: &str| {
+            if word[..12].iter().any(|b| *b != 0) {
+                return Err(CircuitError::InvalidInput(format!("{} does not fit in 160 bits", name)));
+            }
+            Ok(<[u8; 20]>::try_from(&word[12..]).unwrap())
+        };
+        let u64_word = |word: &[u8; 32], name: &str| {
+            if word[..24].iter().any(|b| *b != 0) {
+                return Err(CircuitError::InvalidInput(format!("{} does not fit in 64 bits", name)));
+            }
+            Ok(u64::from_be_bytes(word[24..].try_into().unwrap()))
+        };
+
+        Ok(Self {
+            merkle_root: field_word(&words[MERKLE_ROOT_ROW], "Merkle root")?,
+            nullifier: field_word(&words[NULLIFIER_ROW], "Nullifier")?,
+            recipient: address_word(&words[RECIPIENT_ROW], "Recipient")?,
+            amount: u64_word(&words[AMOUNT_ROW], "Amount")?,
+            vault_address: address_word(&words[VAULT_ROW], "Vault address")?,
+            chain_id: u64_word(&words[CHAIN_ID_ROW], "Chain id")?,
+        })
+    }
+}
+
+/// `uint256(uint160(address))`: the address bytes as a big-endian integer.
+fn address_field<F: PrimeField>(address: &[u8; 20]) -> F {
+    let mut bytes = [0u8; 32];
+    bytes[..20].copy_from_slice(address);
+    bytes[..20].reverse();
+    field_from_bytes::<F>(&bytes)
+}
+
+impl WithdrawalWitness {
+    /// Public inputs this witness satisfies, with the root and nullifier in
+    /// little-endian field encoding.
+    pub fn public_inputs<F: PrimeField>(&self, recipient: [u8; 20]) -> WithdrawalPublicInputs {
+        WithdrawalPublicInputs {
+            merkle_root: field_to_bytes(self.merkle_root::<F>()),
+            nullifier: field_to_bytes(self.nullifier::<F>()),
+            recipient,
+            amount: self.amount,
+            vault_address: [0u8; 20],
+            chain_id: 0,
+        }
+    }
+
+    /// Runs the same relations the circuit enforces, so a bad witness is
+    /// reported before any proving work starts.
+    pub fn validate<F: PrimeField>(&self, public_inputs: &WithdrawalPublicInputs) -> Result<(), CircuitError> {
+        if field_to_bytes(self.merkle_root::<F>()) != public_inputs.merkle_root {
+            return Err(CircuitError::RootMismatch);
+        }
+        if field_to_bytes(self.nullifier::<F>()) != public_inputs.nullifier {
+            return Err(CircuitError::NullifierMismatch);
+        }
+        if self.amount != public_inputs.amount {
+            return Err(CircuitError::AmountMismatch);
+        }
+        if self.path_index() != self.leaf_index as u64 {
+            return Err(CircuitError::LeafIndexMismatch);
+        }
+        Ok(())
+    }
+
+    /// Index spelled out by the first `MERKLE_DEPTH` path bits, little-endian.
+    fn path_index(&self) -> u64 {
+        (0..MERKLE_DEPTH)
+            .filter(|level| self.path_indices.get(*level).copied().unwrap_or(false))
+            .fold(0u64, |index, level| index | (1u64 << level))
+    }
+
+    fn merkle_root<F: PrimeField>(&self) -> F {
+        let leaf = compute_commitment(
+            field_from_bytes::<F>(&self.secret),
+            field_from_bytes::<F>(&self.nullifier_seed),
+        );
+        let zeros = zero_hashes::<F>(MERKLE_DEPTH);
+        let siblings: Vec<F> = (0..MERKLE_DEPTH)
+            .map(|level| self.merkle_path.get(level).map(field_from_bytes::<F>).unwrap_or(zeros[level]))
+            .collect();
+        let indices: Vec<bool> = (0..MERKLE_DEPTH)
+            .map(|level| self.path_indices.get(level).copied().unwrap_or(false))
+            .collect();
+        fold_merkle_path(leaf, &siblings, &indices, |left, right| poseidon_hash_native(&[*left, *right]))
+    }
+
+    fn nullifier<F: PrimeField>(&self) -> F {
+        compute_nullifier(field_from_bytes::<F>(&self.nullifier_seed), F::from(self.leaf_index as u64))
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -62,6 +230,11 @@ impl<F: PrimeField> WithdrawalCircuit<F> {
             _marker: PhantomData,
         }
     }
+
+    /// Number of values expected in each instance column.
+    pub fn num_instances() -> Vec<usize> {
+        vec![6]
+    }
 }
 
 impl<F: PrimeField> Circuit<F> for WithdrawalCircuit<F> {
@@ -84,65 +257,38 @@ impl<F: PrimeField> Circuit<F> for WithdrawalCircuit<F> {
         let fixed = meta.fixed_column();
         let instance = meta.instance_column();
         
+        meta.enable_constant(fixed);
         meta.enable_equality(instance);
         for col in advice.iter() {
             meta.enable_equality(*col);
         }
 
-        let s_hash = meta.selector();
-        let s_merkle = meta.selector();
-        let s_nullifier = meta.selector();
-
-        meta.create_gate("poseidon_hash", |meta| {
-            let s = meta.query_selector(s_hash);
-            let left = meta.query_advice(advice[0], Rotation::cur());
-            let right = meta.query_advice(advice[1], Rotation::cur());
-            let output = meta.query_advice(advice[2], Rotation::cur());
-            
-            let two = Expression::Constant(F::from(2u64));
-            let three = Expression::Constant(F::from(3u64));
-            let computed = left.clone() * left + right.clone() * right * two + three;
-            
-            vec![s * (output - computed)]
-        });
+        let s_recipient = meta.selector();
 
-        meta.create_gate("merkle_step", |meta| {
-            let s = meta.query_selector(s_merkle);
-            let current = meta.query_advice(advice[0], Rotation::cur());
-            let sibling = meta.query_advice(advice[1], Rotation::cur());
-            let is_right = meta.query_advice(advice[2], Rotation::cur());
-            let parent = meta.query_advice(advice[3], Rotation::cur());
-            
-            let two = Expression::Constant(F::from(2u64));
-            let three = Expression::Constant(F::from(3u64));
-            let one = Expression::Constant(F::ONE);
-            
-            let left_hash = current.clone() * current.clone() + sibling.clone() * sibling.clone() * two.clone() + three.clone();
-            let right_hash = sibling.clone() * sibling + current.clone() * current * two + three;
-            
-            let computed = is_right.clone() * right_hash + (one - is_right) * left_hash;
-            
-            vec![s * (parent - computed)]
-        });
+        let merkle_path = MerklePathChip::configure(meta, advice);
+
+        // A zero recipient has no inverse, so a proof can never pay out to the
+        // zero address.
+        meta.create_gate("recipient_nonzero", |meta| {
+            let s = meta.query_selector(s_recipient);
+            let recipient = meta.query_advice(advice[3], Rotation::cur());
+            let recipient_inv = meta.query_advice(advice[4], Rotation::cur());
 
-        meta.create_gate("nullifier_derivation", |meta| {
-            let s = meta.query_selector(s_nullifier);
-            let seed = meta.query_advice(advice[0], Rotation::cur());
-            let index = meta.query_advice(advice[1], Rotation::cur());
-            let nullifier = meta.query_advice(advice[2], Rotation::cur());
-            
-            let computed = seed.clone() * seed + index;
-            
-            vec![s * (nullifier - computed)]
+            vec![s * (recipient * recipient_inv - Expression::Constant(F::ONE))]
         });
 
+        let round_constants = [meta.fixed_column(), meta.fixed_column(), meta.fixed_column()];
+        let poseidon = PoseidonChip::configure(meta, [advice[0], advice[1], advice[2]], round_constants);
+        let range_check = RangeCheckChip::configure(meta, advice[3], advice[4]);
+
         WithdrawalConfig {
             advice,
             fixed,
             instance,
-            s_hash,
-            s_merkle,
-            s_nullifier,
+            s_recipient,
+            merkle_path,
+            poseidon,
+            range_check,
         }
     }
 
@@ -154,7 +300,7 @@ impl<F: PrimeField> Circuit<F> for WithdrawalCircuit<F> {
         let witness = self.witness.as_ref();
         let public_inputs = self.public_inputs.as_ref();
         
-        layouter.assign_region(
+        let (secret, nullifier_seed, leaf_index, recipient, amount, vault_address, chain_id) = layouter.assign_region(
             || "withdrawal_proof",
             |mut region| {
                 let mut row = 0;
@@ -173,19 +319,6 @@ impl<F: PrimeField> Circuit<F> for WithdrawalCircuit<F> {
                     || witness.map(|w| bytes_to_field::<F>(&w.nullifier_seed)).unwrap_or(Value::unknown()),
                 )?;
 
-                config.s_hash.enable(&mut region, row)?;
-                
-                let commitment = region.assign_advice(
-                    || "commitment",
-                    config.advice[2],
-                    row,
-                    || {
-                        secret.value().zip(nullifier_seed.value()).map(|(s, n)| {
-                            *s * *s + *n * *n * F::from(2u64) + F::from(3u64)
-                        })
-                    },
-                )?;
-                
                 row += 1;
 
                 let leaf_index = region.assign_advice(
@@ -195,117 +328,116 @@ impl<F: PrimeField> Circuit<F> for WithdrawalCircuit<F> {
                     || witness.map(|w| Value::known(F::from(w.leaf_index as u64))).unwrap_or(Value::unknown()),
                 )?;
                 
-                let _nullifier_seed_copy = region.assign_advice(
-                    || "nullifier_seed_copy",
-                    config.advice[0],
-                    row,
-                    || nullifier_seed.value().copied(),
+                let amount = region.assign_advice(
+                    || "amount",
+                    config.advice[4],
+                    0,
+                    || witness.map(|w| Value::known(F::from(w.amount))).unwrap_or(Value::unknown()),
                 )?;
-                
-                config.s_nullifier.enable(&mut region, row)?;
-                
-                let _nullifier = region.assign_advice(
-                    || "nullifier",
-                    config.advice[2],
+
+                config.s_recipient.enable(&mut region, row)?;
+
+                let recipient = region.assign_advice(
+                    || "recipient",
+                    config.advice[3],
                     row,
-                    || {
-                        nullifier_seed.value().zip(leaf_index.value()).map(|(seed, idx)| {
-                            *seed * *seed + *idx
-                        })
-                    },
+                    || public_inputs.map(|p| Value::known(address_field::<F>(&p.recipient))).unwrap_or(Value::unknown()),
                 )?;
-                
-                row += 1;
 
-                let mut current_hash = commitment;
-                
-                for level in 0..MERKLE_DEPTH {
-                    let sibling = region.assign_advice(
-                        || format!("sibling_{}", level),
-                        config.advice[1],
-                        row,
-                        || {
-                            witness.map(|w| {
-                                if level < w.merkle_path.len() {
-                                    bytes_to_field::<F>(&w.merkle_path[level])
-                                } else {
-                                    Value::known(F::ZERO)
-                                }
-                            }).unwrap_or(Value::unknown())
-                        },
-                    )?;
-                    
-                    let is_right = region.assign_advice(
-                        || format!("is_right_{}", level),
-                        config.advice[2],
-                        row,
-                        || {
-                            witness.map(|w| {
-                                if level < w.path_indices.len() && w.path_indices[level] {
-                                    Value::known(F::ONE)
-                                } else {
-                                    Value::known(F::ZERO)
-                                }
-                            }).unwrap_or(Value::unknown())
-                        },
-                    )?;
-                    
-                    let _current_copy = region.assign_advice(
-                        || format!("current_{}", level),
-                        config.advice[0],
-                        row,
-                        || current_hash.value().copied(),
-                    )?;
-                    
-                    config.s_merkle.enable(&mut region, row)?;
-                    
-                    let parent = region.assign_advice(
-                        || format!("parent_{}", level),
-                        config.advice[3],
-                        row,
-                        || {
-                            current_hash.value().zip(sibling.value()).zip(is_right.value()).map(|((curr, sib), right)| {
-                                let left_hash = *curr * *curr + *sib * *sib * F::from(2u64) + F::from(3u64);
-                                let right_hash = *sib * *sib + *curr * *curr * F::from(2u64) + F::from(3u64);
-                                if *right == F::ONE {
-                                    right_hash
-                                } else {
-                                    left_hash
-                                }
-                            })
-                        },
-                    )?;
-                    
-                    current_hash = parent;
-                    row += 1;
-                }
-
-                let _amount = region.assign_advice(
-                    || "amount",
+                region.assign_advice(
+                    || "recipient_inv",
                     config.advice[4],
-                    0,
-                    || witness.map(|w| Value::known(F::from(w.amount))).unwrap_or(Value::unknown()),
+                    row,
+                    || recipient.value().map(|r| Option::<F>::from(r.invert()).unwrap_or(F::ZERO)),
                 )?;
 
-                let _ = public_inputs;
+                let vault_address = region.assign_advice(
+                    || "vault_address",
+                    config.advice[0],
+                    row,
+                    || public_inputs.map(|p| Value::known(address_field::<F>(&p.vault_address))).unwrap_or(Value::unknown()),
+                )?;
 
-                Ok(())
+                let chain_id = region.assign_advice(
+                    || "chain_id",
+                    config.advice[2],
+                    row,
+                    || public_inputs.map(|p| Value::known(F::from(p.chain_id))).unwrap_or(Value::unknown()),
+                )?;
+
+                Ok((secret, nullifier_seed, leaf_index, recipient, amount, vault_address, chain_id))
             },
         )?;
 
+        let indices: Vec<Value<bool>> = (0..MERKLE_DEPTH)
+            .map(|level| {
+                witness
+                    .map(|w| Value::known(w.path_indices.get(level).copied().unwrap_or(false)))
+                    .unwrap_or(Value::unknown())
+            })
+            .collect();
+        let zeros = zero_hashes::<F>(MERKLE_DEPTH);
+        let siblings: Vec<Value<F>> = (0..MERKLE_DEPTH)
+            .map(|level| {
+                witness
+                    .map(|w| Value::known(w.merkle_path.get(level).map(field_from_bytes::<F>).unwrap_or(zeros[level])))
+                    .unwrap_or(Value::unknown())
+            })
+            .collect();
+
+        let merkle_path = MerklePathChip::<F>::construct(config.merkle_path.clone());
+        let path_bits = merkle_path.path_bits(layouter.namespace(|| "path_index_bits"), &leaf_index, &indices)?;
+
+        let poseidon = PoseidonChip::<F>::construct(config.poseidon.clone());
+        let commitment = poseidon.hash(layouter.namespace(|| "commitment"), &[secret, nullifier_seed.clone()])?;
+        let nullifier = poseidon.hash(layouter.namespace(|| "nullifier"), &[nullifier_seed, leaf_index])?;
+        let current_hash = merkle_path.root(layouter.namespace(|| "merkle_path"), &poseidon, commitment, &path_bits, &siblings)?;
+
+        RangeCheckChip::construct(config.range_check.clone())
+            .range_check(layouter.namespace(|| "amount_range"), &amount, AMOUNT_BITS)?;
+
+        layouter.constrain_instance(current_hash.cell(), config.instance, MERKLE_ROOT_ROW)?;
+        layouter.constrain_instance(nullifier.cell(), config.instance, NULLIFIER_ROW)?;
+        layouter.constrain_instance(recipient.cell(), config.instance, RECIPIENT_ROW)?;
+        layouter.constrain_instance(amount.cell(), config.instance, AMOUNT_ROW)?;
+        layouter.constrain_instance(vault_address.cell(), config.instance, VAULT_ROW)?;
+        layouter.constrain_instance(chain_id.cell(), config.instance, CHAIN_ID_ROW)?;
+
         Ok(())
     }
 }
 
-fn bytes_to_field<F: PrimeField>(bytes: &[u8; 32]) -> Value<F> {
-    let mut acc = F::ZERO;
-    let base = F::from(256u64);
-    for byte in bytes.iter().take(31) {
-        acc = acc * base + F::from(*byte as u64);
+/// The deposit commitment, `Poseidon(secret, nullifier_seed)`. The circuit
+/// hashes the same two cells with the Poseidon chip to get the merkle leaf.
+pub fn compute_commitment<F: PrimeField>(secret: F, nullifier_seed: F) -> F {
+    poseidon_hash_native(&[secret, nullifier_seed])
+}
+
+/// The one nullifier derivation: Poseidon over the seed and the leaf index.
+/// The circuit hashes the same two cells with the Poseidon chip.
+pub fn compute_nullifier<F: PrimeField>(nullifier_seed: F, leaf_index: F) -> F {
+    poseidon_hash_native(&[nullifier_seed, leaf_index])
+}
+
+/// Native counterpart of one merkle level: the `merkle_step` gate orders the
+/// pair and the Poseidon chip hashes it.
+pub fn compute_merkle_parent<F: PrimeField>(current: F, sibling: F, is_right: bool) -> F {
+    fold_merkle_path(current, &[sibling], &[is_right], |left, right| poseidon_hash_native(&[*left, *right]))
+}
+
+/// Roots of empty subtrees: `zero_hashes(d)[0]` is the empty leaf and entry
+/// `i + 1` is the parent of two copies of entry `i`. Missing path siblings
+/// stand for empty subtrees, so they take these values rather than zero.
+pub fn zero_hashes<F: PrimeField>(depth: usize) -> Vec<F> {
+    let mut zeros = Vec::with_capacity(depth + 1);
+    zeros.push(F::ZERO);
+    for level in 0..depth {
+        zeros.push(poseidon_hash_native(&[zeros[level], zeros[level]]));
     }
-    Value::known(acc)
+    zeros
 }
 
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -314,6 +446,12 @@ mod tests {
         halo2curves::bn256::Fr,
     };
 
+    fn mock_prover(witness: WithdrawalWitness, public_inputs: WithdrawalPublicInputs) -> MockProver<Fr> {
+        let instances = public_inputs.instances::<Fr>().unwrap();
+        let circuit = WithdrawalCircuit::<Fr>::new(witness, public_inputs);
+        MockProver::run(11, &circuit, vec![instances]).unwrap()
+    }
+
     #[test]
     fn test_minimal_withdrawal_circuit() {
         let witness = WithdrawalWitness {
@@ -325,13 +463,43 @@ mod tests {
             path_indices: vec![false; MERKLE_DEPTH],
         };
         
-        let public_inputs = WithdrawalPublicInputs::default();
+        let public_inputs = witness.public_inputs::<Fr>([0xab; 20]);
         
-        let circuit = WithdrawalCircuit::<Fr>::new(witness, public_inputs);
-        let prover = MockProver::run(10, &circuit, vec![vec![]]).unwrap();
+        let prover = mock_prover(witness, public_inputs);
         prover.verify().unwrap();
     }
 
+    #[test]
+    fn test_zero_hashes() {
+        let zeros = zero_hashes::<Fr>(MERKLE_DEPTH);
+        assert_eq!(zeros.len(), MERKLE_DEPTH + 1);
+        assert_eq!(zeros[0], Fr::from(0u64));
+        assert_ne!(zeros[MERKLE_DEPTH], Fr::from(0u64));
+        assert_eq!(zeros, zero_hashes::<Fr>(MERKLE_DEPTH));
+        assert_eq!(zero_hashes::<Fr>(3)[..], zeros[..4]);
+        for level in 0..MERKLE_DEPTH {
+            assert_eq!(compute_merkle_parent(zeros[level], zeros[level], false), zeros[level + 1]);
+        }
+
+        // An omitted path means every sibling is an empty subtree.
+        let short = WithdrawalWitness {
+            secret: [1u8; 32],
+            nullifier_seed: [2u8; 32],
+            amount: 0,
+            leaf_index: 0,
+            merkle_path: vec![],
+            path_indices: vec![],
+        };
+        let explicit = WithdrawalWitness {
+            merkle_path: zeros[..MERKLE_DEPTH].iter().map(|z| field_to_bytes(*z)).collect(),
+            path_indices: vec![false; MERKLE_DEPTH],
+            ..short.clone()
+        };
+        let public_inputs = short.public_inputs::<Fr>([0xab; 20]);
+        assert_eq!(public_inputs.merkle_root, explicit.public_inputs::<Fr>([0xab; 20]).merkle_root);
+        mock_prover(short, public_inputs).verify().unwrap();
+    }
+
     #[test]
     fn test_full_withdrawal_circuit() {
         let witness = WithdrawalWitness {
@@ -340,18 +508,13 @@ mod tests {
             amount: 1_000_000_000_000_000_000,
             leaf_index: 5,
             merkle_path: vec![[0u8; 32]; MERKLE_DEPTH],
-            path_indices: vec![false; MERKLE_DEPTH],
+            path_indices: index_bits(5),
         };
         
-        let public_inputs = WithdrawalPublicInputs {
-            merkle_root: [0u8; 32],
-            nullifier: [0u8; 32],
-            recipient: [0xab; 20],
-            amount: 1_000_000_000_000_000_000,
-        };
+        let public_inputs = witness.public_inputs::<Fr>([0xab; 20]);
+        assert_eq!(public_inputs.amount, 1_000_000_000_000_000_000);
         
-        let circuit = WithdrawalCircuit::<Fr>::new(witness, public_inputs);
-        let prover = MockProver::run(10, &circuit, vec![vec![]]).unwrap();
+        let prover = mock_prover(witness, public_inputs);
         prover.verify().unwrap();
     }
 
@@ -370,10 +533,249 @@ mod tests {
         witness.path_indices[1] = true;
         witness.path_indices[2] = true;
         
-        let public_inputs = WithdrawalPublicInputs::default();
+        let public_inputs = witness.public_inputs::<Fr>([0xab; 20]);
         
-        let circuit = WithdrawalCircuit::<Fr>::new(witness, public_inputs);
-        let prover = MockProver::run(10, &circuit, vec![vec![]]).unwrap();
+        let prover = mock_prover(witness, public_inputs);
         prover.verify().unwrap();
     }
+
+    /// Root and path of `leaves[index]` in a depth-`MERKLE_DEPTH` tree hashed
+    /// with `poseidon_hash_native`, padding each level with the empty subtree.
+    fn poseidon_tree(leaves: &[Fr], index: usize) -> (Fr, Vec<[u8; 32]>) {
+        let mut level = leaves.to_vec();
+        let mut empty = Fr::from(0u64);
+        let mut position = index;
+        let mut path = Vec::with_capacity(MERKLE_DEPTH);
+
+        for _ in 0..MERKLE_DEPTH {
+            if level.len() % 2 == 1 {
+                level.push(empty);
+            }
+            path.push(field_to_bytes(level[position ^ 1]));
+            level = level.chunks(2).map(|pair| poseidon_hash_native(&[pair[0], pair[1]])).collect();
+            empty = poseidon_hash_native(&[empty, empty]);
+            position /= 2;
+        }
+        (level[0], path)
+    }
+
+    #[test]
+    fn test_path_matches_native_poseidon_tree() {
+        let secret = [7u8; 32];
+        let nullifier_seed = [8u8; 32];
+        let commitment = compute_commitment(field_from_bytes::<Fr>(&secret), field_from_bytes::<Fr>(&nullifier_seed));
+
+        let mut leaves: Vec<Fr> = (0..8u64).map(|i| Fr::from(1_000 + i)).collect();
+        leaves[5] = commitment;
+        let (root, path) = poseidon_tree(&leaves, 5);
+
+        let witness = WithdrawalWitness {
+            secret,
+            nullifier_seed,
+            amount: 10,
+            leaf_index: 5,
+            merkle_path: path,
+            path_indices: index_bits(5),
+        };
+        let public_inputs = witness.public_inputs::<Fr>([0xab; 20]);
+        assert_eq!(public_inputs.merkle_root, field_to_bytes(root));
+        witness.validate::<Fr>(&public_inputs).unwrap();
+
+        let mut wrong_sibling = witness.clone();
+        wrong_sibling.merkle_path[2][0] ^= 1;
+        assert!(matches!(wrong_sibling.validate::<Fr>(&public_inputs), Err(CircuitError::RootMismatch)));
+
+        let prover = mock_prover(witness, public_inputs);
+        prover.verify().unwrap();
+    }
+
+    #[test]
+    fn test_rejects_tampered_public_inputs() {
+        let witness = sample_witness();
+        let public_inputs = witness.public_inputs::<Fr>([0xab; 20]);
+        let instances = public_inputs.instances::<Fr>().unwrap();
+        let circuit = WithdrawalCircuit::<Fr>::new(witness, public_inputs);
+
+        MockProver::run(11, &circuit, vec![instances.clone()]).unwrap().verify().unwrap();
+        assert!(MockProver::run(11, &circuit, vec![vec![]]).unwrap().verify().is_err());
+
+        for row in 0..instances.len() {
+            let mut tampered = instances.clone();
+            tampered[row] += Fr::from(1u64);
+            let prover = MockProver::run(11, &circuit, vec![tampered]).unwrap();
+            assert!(prover.verify().is_err(), "instance row {} is not bound", row);
+        }
+    }
+
+    #[test]
+    fn test_rejects_zero_recipient() {
+        let witness = sample_witness();
+        let mut one = [0u8; 20];
+        one[0] = 1;
+        mock_prover(witness.clone(), witness.public_inputs::<Fr>(one)).verify().unwrap();
+
+        let zero = witness.public_inputs::<Fr>([0u8; 20]);
+        assert!(matches!(zero.instances::<Fr>(), Err(CircuitError::InvalidInput(_))));
+
+        // Even with matching instances supplied by hand the gate has no
+        // satisfying inverse.
+        let mut instances = witness.public_inputs::<Fr>(one).instances::<Fr>().unwrap();
+        instances[RECIPIENT_ROW] = Fr::from(0u64);
+        let circuit = WithdrawalCircuit::<Fr>::new(witness, zero);
+        assert!(MockProver::run(11, &circuit, vec![instances]).unwrap().verify().is_err());
+    }
+
+    #[test]
+    fn test_proof_is_bound_to_deployment() {
+        let witness = sample_witness();
+        let vault_a = witness.public_inputs::<Fr>([0xab; 20]).bound_to([0xa0; 20], 1);
+        let instances_a = vault_a.instances::<Fr>().unwrap();
+        assert_eq!(instances_a.len(), WithdrawalCircuit::<Fr>::num_instances()[0]);
+
+        let circuit = WithdrawalCircuit::<Fr>::new(witness, vault_a.clone());
+        MockProver::run(11, &circuit, vec![instances_a]).unwrap().verify().unwrap();
+
+        for other in [vault_a.clone().bound_to([0xb0; 20], 1), vault_a.clone().bound_to([0xa0; 20], 10)] {
+            let instances = other.instances::<Fr>().unwrap();
+            assert!(MockProver::run(11, &circuit, vec![instances]).unwrap().verify().is_err());
+        }
+
+        // Inputs that don't name a deployment are refused rather than bound
+        // to vault zero on chain zero.
+        let unbound = r#"{"merkle_root":[0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],"nullifier":[0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],"recipient":[1,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],"amount":5}"#;
+        assert!(serde_json::from_str::<WithdrawalPublicInputs>(unbound).is_err());
+    }
+
+    /// Shared with contracts/test/WithdrawalPublicInputs.t.sol: the words
+    /// `PrivacyVault` builds for these values, which the instances must equal.
+    #[test]
+    fn test_instances_match_contract_public_inputs() {
+        let public_inputs = WithdrawalPublicInputs {
+            merkle_root: field_to_bytes(Fr::from(0x0a0b0cu64)),
+            nullifier: field_to_bytes(Fr::from(0x010203u64)),
+            recipient: hex::decode("0102030405060708090a0b0c0d0e0f1011121314").unwrap().try_into().unwrap(),
+            amount: 1_000_000_000_000_000_000,
+            vault_address: hex::decode("5215d0bf334668c5722bc94fef1f82d95443cf57").unwrap().try_into().unwrap(),
+            chain_id: 845_320_009,
+        };
+        let expected = [
+            "00000000000000000000000000000000000000000000000000000000000a0b0c",
+            "0000000000000000000000000000000000000000000000000000000000010203",
+            "0000000000000000000000000102030405060708090a0b0c0d0e0f1011121314",
+            "0000000000000000000000000000000000000000000000000de0b6b3a7640000",
+            "0000000000000000000000005215d0bf334668c5722bc94fef1f82d95443cf57",
+            "0000000000000000000000000000000000000000000000000000000032628f49",
+        ];
+
+        let words = public_inputs.evm_words().unwrap();
+        for (word, expected) in words.iter().zip(expected) {
+            assert_eq!(hex::encode(word), expected);
+        }
+        assert_eq!(public_inputs.instances::<Fr>().unwrap()[RECIPIENT_ROW], Fr::from_u128(0x0102030405060708090a0b0c0d0e0f10) * Fr::from(1u64 << 32) + Fr::from(0x11121314u64));
+        assert_eq!(WithdrawalPublicInputs::from_evm_words(&words).unwrap(), public_inputs);
+
+        let mut wide = words;
+        wide[AMOUNT_ROW][0] = 1;
+        assert!(WithdrawalPublicInputs::from_evm_words(&wide).is_err());
+    }
+
+    #[test]
+    fn test_witness_secrets_are_zeroized() {
+        fn assert_zeroize_on_drop<T: ZeroizeOnDrop>() {}
+        assert_zeroize_on_drop::<WithdrawalWitness>();
+
+        let mut witness = sample_witness();
+        witness.zeroize();
+        assert_eq!(witness.secret, [0u8; 32]);
+        assert_eq!(witness.nullifier_seed, [0u8; 32]);
+        assert_eq!((witness.amount, witness.leaf_index), (0, 0));
+        assert!(witness.merkle_path.is_empty() && witness.path_indices.is_empty());
+    }
+
+    fn index_bits(index: u32) -> Vec<bool> {
+        (0..MERKLE_DEPTH).map(|level| (index >> level) & 1 == 1).collect()
+    }
+
+    #[test]
+    fn test_rejects_leaf_index_inconsistent_with_path_bits() {
+        let witness = WithdrawalWitness {
+            secret: [1u8; 32],
+            nullifier_seed: [2u8; 32],
+            amount: 10,
+            leaf_index: 5,
+            merkle_path: vec![[0u8; 32]; MERKLE_DEPTH],
+            path_indices: index_bits(4),
+        };
+        let public_inputs = witness.public_inputs::<Fr>([0xab; 20]);
+        assert!(matches!(witness.validate::<Fr>(&public_inputs), Err(CircuitError::LeafIndexMismatch)));
+
+        let prover = mock_prover(witness, public_inputs);
+        assert!(prover.verify().is_err());
+
+        let beyond_depth = WithdrawalWitness {
+            leaf_index: 1 << MERKLE_DEPTH,
+            path_indices: vec![false; MERKLE_DEPTH],
+            merkle_path: vec![[0u8; 32]; MERKLE_DEPTH],
+            ..Default::default()
+        };
+        let public_inputs = beyond_depth.public_inputs::<Fr>([0xab; 20]);
+        let prover = mock_prover(beyond_depth, public_inputs);
+        assert!(prover.verify().is_err());
+    }
+
+    fn sample_witness() -> WithdrawalWitness {
+        WithdrawalWitness {
+            secret: [9u8; 32],
+            nullifier_seed: [4u8; 32],
+            amount: 42,
+            leaf_index: 3,
+            merkle_path: vec![[1u8; 32]; MERKLE_DEPTH],
+            path_indices: index_bits(3),
+        }
+    }
+
+    #[test]
+    fn test_nullifier_is_poseidon_of_seed_and_index() {
+        let witness = sample_witness();
+        let expected = poseidon_hash_native(&[field_from_bytes::<Fr>(&witness.nullifier_seed), Fr::from(3u64)]);
+        assert_eq!(compute_nullifier(field_from_bytes::<Fr>(&witness.nullifier_seed), Fr::from(3u64)), expected);
+
+        let public_inputs = witness.public_inputs::<Fr>([0xab; 20]);
+        assert_eq!(public_inputs.nullifier, field_to_bytes(expected));
+
+        // The tree-built witness for the same deposit yields the same nullifier.
+        let deposit = crate::commitment::generate_deposit();
+        let mut tree = crate::merkle::PoseidonMerkleTree::empty(MERKLE_DEPTH);
+        tree.insert(3, deposit.commitment).unwrap();
+        let from_tree = tree.withdrawal_witness(3, deposit.secret, deposit.nullifier_seed, 1).unwrap();
+        assert_eq!(
+            from_tree.public_inputs::<Fr>([0xab; 20]).nullifier,
+            field_to_bytes(compute_nullifier(field_from_bytes::<Fr>(&deposit.nullifier_seed), Fr::from(3u64)))
+        );
+
+        mock_prover(witness, public_inputs).verify().unwrap();
+    }
+
+    #[test]
+    fn test_validate_accepts_matching_inputs() {
+        let witness = sample_witness();
+        let public_inputs = witness.public_inputs::<Fr>([0xab; 20]);
+        assert!(witness.validate::<Fr>(&public_inputs).is_ok());
+    }
+
+    #[test]
+    fn test_validate_rejects_root_mismatch() {
+        let witness = sample_witness();
+        let mut public_inputs = witness.public_inputs::<Fr>([0xab; 20]);
+        public_inputs.merkle_root[0] ^= 1;
+        assert!(matches!(witness.validate::<Fr>(&public_inputs), Err(CircuitError::RootMismatch)));
+    }
+
+    #[test]
+    fn test_validate_rejects_nullifier_mismatch() {
+        let witness = sample_witness();
+        let mut public_inputs = witness.public_inputs::<Fr>([0xab; 20]);
+        public_inputs.nullifier[0] ^= 1;
+        assert!(matches!(witness.validate::<Fr>(&public_inputs), Err(CircuitError::NullifierMismatch)));
+    }
 }
@@ -0,0 +1,79 @@
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::process::Command;
+use halo2_proofs::{halo2curves::bn256::Fr, poly::commitment::Params};
+use zkenclave_circuits::{
+    keygen::write_vk, prove_withdrawal, setup_withdrawal_circuit,
+    withdrawal_circuit::{WithdrawalCircuit, WithdrawalWitness, MERKLE_DEPTH},
+};
+
+fn scratch_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("zkenclave-verify-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn run_verify(dir: &PathBuf, inputs_path: &PathBuf, proof_path: &PathBuf) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_verify"))
+        .arg("--params").arg(dir.join("params.bin"))
+        .arg("--vk").arg(dir.join("withdrawal_vk.bin"))
+        .arg("--public-inputs").arg(inputs_path)
+        .arg("--proof").arg(proof_path)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn verify_binary_accepts_native_proof() {
+    let dir = scratch_dir();
+    let (prover, verifier) = setup_withdrawal_circuit(11).unwrap();
+
+    verifier.params.write(&mut File::create(dir.join("params.bin")).unwrap()).unwrap();
+    write_vk::<WithdrawalCircuit<Fr>, _>(&verifier.vk, File::create(dir.join("withdrawal_vk.bin")).unwrap()).unwrap();
+
+    let witness = WithdrawalWitness {
+        secret: [1u8; 32],
+        nullifier_seed: [2u8; 32],
+        amount: 10,
+        leaf_index: 0,
+        merkle_path: vec![[0u8; 32]; MERKLE_DEPTH],
+        path_indices: vec![false; MERKLE_DEPTH],
+    };
+    let public_inputs = witness.public_inputs::<Fr>([0xab; 20]);
+    let instances = public_inputs.instances::<Fr>().unwrap();
+    let proof = prove_withdrawal(&prover, WithdrawalCircuit::new(witness, public_inputs.clone()), &[&instances]).unwrap();
+
+    let inputs_path = dir.join("inputs.json");
+    fs::write(&inputs_path, serde_json::to_vec(&public_inputs).unwrap()).unwrap();
+    let proof_path = dir.join("proof.json");
+    fs::write(&proof_path, proof.to_bytes()).unwrap();
+    let output = run_verify(&dir, &inputs_path, &proof_path);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("PASS"));
+
+    let mut tampered = proof.clone();
+    let mid = tampered.bytes.len() / 2;
+    tampered.bytes[mid] ^= 1;
+    let tampered_path = dir.join("tampered.json");
+    fs::write(&tampered_path, tampered.to_bytes()).unwrap();
+    let output = run_verify(&dir, &inputs_path, &tampered_path);
+    assert_eq!(output.status.code(), Some(1));
+
+    // A valid proof for another recipient does not pass for this one.
+    let mut other_recipient = public_inputs.clone();
+    other_recipient.recipient = [0xcd; 20];
+    let other_inputs_path = dir.join("other-inputs.json");
+    fs::write(&other_inputs_path, serde_json::to_vec(&other_recipient).unwrap()).unwrap();
+    let output = run_verify(&dir, &other_inputs_path, &proof_path);
+    assert_eq!(output.status.code(), Some(1));
+
+    // Inputs missing the deployment fields are refused outright.
+    let unbound_path = dir.join("unbound.json");
+    let mut unbound = serde_json::to_value(&public_inputs).unwrap();
+    unbound.as_object_mut().unwrap().remove("vault_address");
+    fs::write(&unbound_path, serde_json::to_vec(&unbound).unwrap()).unwrap();
+    let output = run_verify(&dir, &unbound_path, &proof_path);
+    assert_eq!(output.status.code(), Some(2));
+
+    fs::remove_dir_all(&dir).ok();
+}